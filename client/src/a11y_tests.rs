@@ -0,0 +1,107 @@
+//! Renders the interactive parts of the timeline on the server and checks the markup assistive technology relies on.
+
+use {
+    crate::{
+        create::CreateControl, live::LiveExperiences, offline::PendingCreates, Band, ExperienceCard,
+    },
+    client_api::types::{
+        api::ExperiencesHostname,
+        external::chrono::{TimeZone, Utc},
+        timing::Timing,
+    },
+    leptos::{create_rw_signal, provide_context, ssr::render_to_string, view, Callback, IntoView},
+};
+
+fn render<V: IntoView + 'static>(view: impl FnOnce() -> V + 'static) -> String {
+    render_to_string(move || {
+        provide_context(ExperiencesHostname("https://experiences.example".to_string()));
+        view()
+    })
+    .to_string()
+}
+
+/// Whether the first element containing `needle` is inside a `<label>`.
+fn inside_label(html: &str, needle: &str) -> bool {
+    let Some(position) = html.find(needle) else {
+        return false;
+    };
+    let before = &html[..position];
+    match (before.rfind("<label"), before.rfind("</label>")) {
+        (Some(open), Some(close)) => open > close,
+        (Some(_), None) => true,
+        _ => false,
+    }
+}
+
+#[test]
+fn band_is_a_focusable_button() {
+    let html = render(|| {
+        view! {
+            <Band label="Open Holiday".to_string() click=Callback::new(|_| {})>"Open"</Band>
+        }
+    });
+    assert!(html.contains(r#"role="button""#), "{}", html);
+    assert!(html.contains(r#"tabindex="0""#), "{}", html);
+    assert!(html.contains(r#"aria-label="Open Holiday""#), "{}", html);
+}
+
+#[test]
+fn experience_card_describes_its_cover() {
+    let html = render(|| {
+        view! {
+            <ExperienceCard
+                id="a".to_string()
+                name="Holiday".to_string()
+                color=None
+                icon=None
+                live=LiveExperiences::new()
+            />
+        }
+    });
+    assert!(html.contains("<img"), "{}", html);
+    assert!(html.contains(r#"alt="Holiday""#), "{}", html);
+    assert!(html.contains(r#"aria-label="Open Holiday""#), "{}", html);
+    assert!(html.contains(r#"role="button""#), "{}", html);
+}
+
+#[test]
+fn create_form_labels_its_inputs() {
+    let html = render(|| {
+        let pending = PendingCreates {
+            items: create_rw_signal(Vec::new()),
+        };
+        view! {
+            <CreateControl
+                timing=Timing::Instant(Utc.with_ymd_and_hms(2024, 5, 1, 10, 0, 0).unwrap())
+                live=LiveExperiences::new()
+                pending=pending
+                templates=create_rw_signal(Vec::new())
+                expanded=true
+            />
+        }
+    });
+    assert!(inside_label(&html, r#"placeholder="Name""#), "{}", html);
+    assert!(inside_label(&html, r#"type="datetime-local""#), "{}", html);
+    assert!(inside_label(&html, r#"type="color""#), "{}", html);
+    assert!(html.contains(r#"tabindex="0""#), "{}", html);
+    assert!(html.contains(r#"aria-label="#), "{}", html);
+}
+
+#[test]
+fn collapsed_create_control_names_its_slot() {
+    let html = render(|| {
+        let pending = PendingCreates {
+            items: create_rw_signal(Vec::new()),
+        };
+        view! {
+            <CreateControl
+                timing=Timing::Instant(Utc.with_ymd_and_hms(2024, 5, 1, 10, 0, 0).unwrap())
+                live=LiveExperiences::new()
+                pending=pending
+                templates=create_rw_signal(Vec::new())
+            />
+        }
+    });
+    assert!(html.contains(r#"role="button""#), "{}", html);
+    assert!(html.contains(r#"aria-label="Create 2024-05-01 10:00""#), "{}", html);
+}
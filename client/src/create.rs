@@ -0,0 +1,231 @@
+use {
+    crate::{
+        i18n::{format_timing, t, Message},
+        live::LiveExperiences,
+        offline::PendingCreates,
+        suggest::NameInput,
+        templates::{ExperienceTemplate, TemplateButtons},
+        timezone,
+        upload::UploadDrop,
+        Band, CreateExperienceRequest,
+    },
+    client_api::{
+        style::StyledView,
+        types::{
+            external::chrono::{DateTime, NaiveDateTime, TimeZone, Utc},
+            timing::{TimeRange, Timing},
+        },
+    },
+    leptos::{
+        component, create_node_ref, create_rw_signal, event_target_value, html::Input, view,
+        window, Callback, CollectView, IntoView, RwSignal, Show, Signal, SignalGet,
+        SignalGetUntracked, SignalSet,
+    },
+};
+
+const INPUT_FORMAT: &str = "%Y-%m-%dT%H:%M";
+
+/// Value of a `datetime-local` input, in the time zone of the timeline.
+fn to_input_value(time: &DateTime<Utc>) -> String {
+    time.with_timezone(&timezone::current())
+        .format(INPUT_FORMAT)
+        .to_string()
+}
+
+/// A wall-clock time skipped by a daylight saving change is invalid, a repeated one means its first occurrence.
+fn from_input_value(value: &str) -> Option<DateTime<Utc>> {
+    let local = NaiveDateTime::parse_from_str(value, INPUT_FORMAT).ok()?;
+    timezone::current()
+        .from_local_datetime(&local)
+        .earliest()
+        .map(|v| v.with_timezone(&Utc))
+}
+
+/// Timing picked in the form. An empty end creates an instant. `None` when the input is invalid.
+fn picked_timing(start: &str, end: &str) -> Option<Timing> {
+    let start = from_input_value(start)?;
+    if end.is_empty() {
+        return Some(Timing::Instant(start));
+    }
+    let end = from_input_value(end)?;
+    (start <= end).then(|| Timing::Range(TimeRange { start, end }))
+}
+
+/// Compact "+" in a timeline slot that expands into the create form. The picked time starts at the slot's timing and can be changed freely.
+#[component]
+pub fn CreateControl(
+    timing: Timing,
+    live: LiveExperiences,
+    pending: PendingCreates,
+    templates: RwSignal<Vec<ExperienceTemplate>>,
+    /// Starts with the form open instead of the "+".
+    #[prop(optional)]
+    expanded: bool,
+) -> impl IntoView {
+    let expanded = create_rw_signal(expanded);
+    let slot_label = format_timing(&timing);
+    let (initial_start, initial_end) = match &timing {
+        Timing::Instant(v) => (to_input_value(v), String::new()),
+        Timing::Range(v) => (to_input_value(&v.start), to_input_value(&v.end)),
+    };
+    let start = create_rw_signal(initial_start.clone());
+    let end = create_rw_signal(initial_end.clone());
+    let picked = Signal::derive(move || picked_timing(&start.get(), &end.get()));
+    let reset = move || {
+        start.set(initial_start.clone());
+        end.set(initial_end.clone());
+    };
+
+    let name_ref = create_node_ref::<Input>();
+    let icon_ref = create_node_ref::<Input>();
+    let series_ref = create_node_ref::<Input>();
+    // A color input always has a value, so only a color the user actually picked is sent.
+    let color = create_rw_signal(None::<String>);
+    let parent = create_rw_signal(None::<String>);
+    let template_time = Signal::derive({
+        let timing = timing.clone();
+        move || picked.get().unwrap_or_else(|| timing.clone())
+    });
+    let open_label = format!("{} {}", t(Message::Create), slot_label);
+
+    view! {
+        <Show
+            when=move || expanded.get()
+            fallback=move || view! {
+                <Band label=open_label.clone() click=Callback::new(move |_| expanded.set(true))>"+"</Band>
+            }
+        >
+            <StyledView>
+                <div style="display: flex; flex-direction: row; align-items: center;">
+                    <div style="flex: 1; padding: var(--contentSpacing);color: var(--lightColor);font-size: 0.8em;">
+                        {slot_label.clone()}
+                    </div>
+                    <div style="display: flex;">
+                        <Band
+                            color="var(--accentColor2)"
+                            label=if timezone::uses_browser() {
+                                t(Message::UseConfiguredTimezone)
+                            } else {
+                                t(Message::UseBrowserTimezone)
+                            }
+                            click=Callback::new(move |_| {
+                                // Every card formats its times once, so switching reloads the timeline.
+                                timezone::set_uses_browser(!timezone::uses_browser());
+                                let _ = window().location().reload();
+                            })
+                        >
+                            {timezone::current().name()}
+                        </Band>
+                    </div>
+                    <div style="width: 3em; display: flex;">
+                        <Band
+                            color="var(--accentColor2)"
+                            label=t(Message::Close)
+                            click=Callback::new({
+                                let reset = reset.clone();
+                                move |_| {
+                                    reset();
+                                    expanded.set(false);
+                                }
+                            })
+                        >
+                            "×"
+                        </Band>
+                    </div>
+                </div>
+                <div style="display: flex; flex-direction: row;">
+                    <label style="display: block; flex: 1;">
+                        <span class="visually_hidden">{t(Message::Start)}</span>
+                        <input
+                            type="datetime-local"
+                            class="name_input"
+                            prop:value=move || start.get()
+                            on:input=move |e| start.set(event_target_value(&e))
+                        />
+                    </label>
+                    <label style="display: block; flex: 1;">
+                        <span class="visually_hidden">{t(Message::End)}</span>
+                        <input
+                            type="datetime-local"
+                            class="name_input"
+                            prop:value=move || end.get()
+                            on:input=move |e| end.set(event_target_value(&e))
+                            aria-invalid=move || picked.get().is_none().to_string()
+                        />
+                    </label>
+                </div>
+                <NameInput name_ref=name_ref around=template_time />
+                <TemplateButtons templates=templates name_ref=name_ref time=template_time />
+                <UploadDrop name_ref=name_ref fallback=template_time />
+                <div style="display: flex; flex-direction: row;">
+                    <label style="display: block; flex: 1;">
+                        <span class="visually_hidden">{t(Message::Icon)}</span>
+                        <input
+                            ref=icon_ref
+                            class="name_input"
+                            placeholder=t(Message::Icon)
+                            maxlength="8"
+                        />
+                    </label>
+                    <label style="display: flex; align-items: center; padding: 0 var(--contentSpacing); color: var(--lightColor); background-color: var(--accentColor1);">
+                        <span class="visually_hidden">{t(Message::Color)}</span>
+                        <input
+                            type="color"
+                            on:input=move |e| color.set(Some(event_target_value(&e)))
+                        />
+                    </label>
+                </div>
+                <label style="display: block;">
+                    <span class="visually_hidden">{t(Message::Series)}</span>
+                    <input ref=series_ref class="name_input" placeholder=t(Message::Series) />
+                </label>
+                <label style="display: block;">
+                    <span class="visually_hidden">{t(Message::PartOf)}</span>
+                    <select
+                        class="name_input"
+                        prop:value=move || parent.get().unwrap_or_default()
+                        on:change=move |e| parent.set(Some(event_target_value(&e)).filter(|v| !v.is_empty()))
+                    >
+                        <option value="">{format!("{}: {}", t(Message::PartOf), t(Message::NoParent))}</option>
+                        {move || live
+                            .experiences()
+                            .into_iter()
+                            .map(|(id, name)| view! {
+                                <option value=id>{format!("{}: {}", t(Message::PartOf), name)}</option>
+                            })
+                            .collect_view()}
+                    </select>
+                </label>
+                <Band click=Callback::new({
+                    let reset = reset.clone();
+                    move |_| {
+                        let Some(time) = picked.get_untracked() else {
+                            let _ = window().alert_with_message(t(Message::InvalidTime));
+                            return;
+                        };
+                        let name = name_ref.get().unwrap();
+                        let icon = icon_ref.get().unwrap();
+                        let series = series_ref.get().unwrap();
+                        pending.create(
+                            CreateExperienceRequest {
+                                name: name.value(),
+                                time,
+                                tags: Vec::new(),
+                                idempotency_key: None,
+                                color: color.get_untracked(),
+                                icon: Some(icon.value()).filter(|v| !v.trim().is_empty()),
+                                parent_id: parent.get_untracked(),
+                                series_name: Some(series.value()).filter(|v| !v.trim().is_empty()),
+                            },
+                            live,
+                        );
+                        color.set(None);
+                        parent.set(None);
+                        reset();
+                        expanded.set(false);
+                    }
+                })>{t(Message::Create)}</Band>
+            </StyledView>
+        </Show>
+    }
+}
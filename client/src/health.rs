@@ -0,0 +1,95 @@
+use {
+    crate::{
+        i18n::{t, Message},
+        mounted::FirstMounted,
+    },
+    leptos::{component, create_rw_signal, view, IntoView, RwSignal, Show, SignalGet},
+    serde::{Deserialize, Serialize},
+};
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum HealthStatus {
+    Ok,
+    Down,
+    Unknown,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ComponentHealth {
+    pub status: HealthStatus,
+    pub latency_ms: u64,
+    pub message: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct HealthReport {
+    pub reachability: ComponentHealth,
+    pub authentication: ComponentHealth,
+    pub database: ComponentHealth,
+}
+
+impl HealthReport {
+    /// Human readable descriptions of every failing check.
+    pub fn problems(&self) -> Vec<String> {
+        [
+            (t(Message::ServerUnreachable), &self.reachability),
+            (t(Message::PasswordRejected), &self.authentication),
+            (t(Message::DatabaseUnavailable), &self.database),
+        ]
+        .into_iter()
+        .filter(|(_, v)| v.status == HealthStatus::Down)
+        .map(|(label, v)| match &v.message {
+            Some(message) => format!("{}: {}", label, message),
+            None => label.to_string(),
+        })
+        .collect()
+    }
+}
+
+/// Latest `/health` report and the banners that could show it.
+#[derive(Clone, Copy)]
+pub struct Health {
+    pub report: RwSignal<Option<HealthReport>>,
+    banners: FirstMounted,
+}
+
+impl Health {
+    pub fn new() -> Self {
+        Health {
+            report: create_rw_signal(None),
+            banners: FirstMounted::new(),
+        }
+    }
+}
+
+impl Default for Health {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Small strip shown while `/health` reports a problem. Every slot of the timeline renders one, but only the topmost
+/// mounted banner is visible, and the next one takes over when it scrolls out.
+#[component]
+pub fn HealthBanner(health: Health) -> impl IntoView {
+    let first = health.banners.register();
+    let problems = move || {
+        health
+            .report
+            .get()
+            .map(|v| v.problems())
+            .unwrap_or_default()
+    };
+    let visible = move || first.get() && !problems().is_empty();
+
+    view! {
+        <Show when=visible>
+            <div
+                role="status"
+                style="padding: calc(var(--contentSpacing) / 2);box-sizing: border-box;width: 100%;background-color: var(--accentColor2);color: var(--lightColor);font-size: 0.8em;"
+            >
+                {move || problems().join(" · ")}
+            </div>
+        </Show>
+    }
+}
@@ -0,0 +1,255 @@
+use {
+    crate::timezone,
+    client_api::types::{external::chrono::DateTime, timing::Timing},
+    js_sys::{Date, Object, Reflect},
+    leptos::{leptos_dom::is_browser, window},
+    wasm_bindgen::JsValue,
+};
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Locale {
+    En,
+    De,
+}
+
+#[derive(Clone, Copy)]
+pub enum Message {
+    Create,
+    Open,
+    Name,
+    Icon,
+    Color,
+    Deleted,
+    PossibleDuplicate,
+    Retry,
+    Discard,
+    Template,
+    UnableToCreate,
+    Menu,
+    MergeInto,
+    MergeConfirm,
+    UnableToMerge,
+    Parts,
+    PartOf,
+    NoParent,
+    Series,
+    Previous,
+    Next,
+    CreateNext,
+    Start,
+    End,
+    Close,
+    InvalidTime,
+    UseBrowserTimezone,
+    UseConfiguredTimezone,
+    DropMedia,
+    UploadFailed,
+    AddMedia,
+    BuiltFrom,
+    SuggestedExperience,
+    SuggestionEvents,
+    Accept,
+    Dismiss,
+    UnableToDismiss,
+    Statistics,
+    Loading,
+    RangeDuration,
+    PerMonth,
+    PerWeek,
+    TopTags,
+    TopNames,
+    LongestGaps,
+    OneYearAgo,
+    YearsAgo,
+    OpenOriginal,
+    WaitingForConnection,
+    Sending,
+    Failed,
+    ServerUnreachable,
+    PasswordRejected,
+    DatabaseUnavailable,
+}
+
+impl Locale {
+    /// Locale of the browser, English if it is not supported or when rendering outside a browser.
+    pub fn current() -> Self {
+        if !is_browser() {
+            return Locale::En;
+        }
+        match window().navigator().language() {
+            Some(v) if v.to_lowercase().starts_with("de") => Locale::De,
+            _ => Locale::En,
+        }
+    }
+
+    pub fn tag(self) -> &'static str {
+        match self {
+            Locale::En => "en",
+            Locale::De => "de",
+        }
+    }
+
+    /// Looks up `message` in this locale's catalog.
+    pub fn translate(self, message: Message) -> &'static str {
+        match self {
+            Locale::En => english(message),
+            Locale::De => german(message),
+        }
+    }
+}
+
+/// Translates `message` into the browser's locale.
+pub fn t(message: Message) -> &'static str {
+    Locale::current().translate(message)
+}
+
+/// Translates a message with a single `{}` placeholder.
+pub fn t_format(message: Message, value: &str) -> String {
+    t(message).replacen("{}", value, 1)
+}
+
+fn english(message: Message) -> &'static str {
+    match message {
+        Message::Create => "Create",
+        Message::Open => "Open",
+        Message::Name => "Name",
+        Message::Icon => "Icon",
+        Message::Color => "Color",
+        Message::Deleted => "Deleted",
+        Message::PossibleDuplicate => "Similar experiences nearby:",
+        Message::Retry => "Retry",
+        Message::Discard => "Discard",
+        Message::Template => "Template",
+        Message::UnableToCreate => "Unable to create Experience",
+        Message::Menu => "More actions",
+        Message::MergeInto => "Merge into…",
+        Message::MergeConfirm => "Merge the experiences? The media is moved and the first one is deleted:",
+        Message::UnableToMerge => "Unable to merge experiences",
+        Message::Parts => "Parts",
+        Message::PartOf => "Part of",
+        Message::NoParent => "No other experience",
+        Message::Series => "Series",
+        Message::Previous => "Previous",
+        Message::Next => "Next",
+        Message::CreateNext => "Create next",
+        Message::Start => "Start",
+        Message::End => "End",
+        Message::Close => "Close",
+        Message::InvalidTime => "The end has to be after the start",
+        Message::UseBrowserTimezone => "Use the time zone of this browser",
+        Message::UseConfiguredTimezone => "Use the time zone of the timeline",
+        Message::DropMedia => "Drop photos or videos here, or choose files",
+        Message::UploadFailed => "Upload failed",
+        Message::AddMedia => "Add media",
+        Message::BuiltFrom => "Built from {} events",
+        Message::SuggestedExperience => "Suggested experience",
+        Message::SuggestionEvents => "{} events",
+        Message::Accept => "Accept",
+        Message::Dismiss => "Dismiss",
+        Message::UnableToDismiss => "Unable to dismiss suggestion",
+        Message::Statistics => "Statistics",
+        Message::Loading => "Loading…",
+        Message::RangeDuration => "{} experiences with a duration, together",
+        Message::PerMonth => "Experiences per month",
+        Message::PerWeek => "Experiences per week",
+        Message::TopTags => "Top tags",
+        Message::TopNames => "Top names",
+        Message::LongestGaps => "Longest gaps",
+        Message::OneYearAgo => "On this day one year ago",
+        Message::YearsAgo => "On this day {} years ago",
+        Message::OpenOriginal => "Open original",
+        Message::WaitingForConnection => "Waiting for connection",
+        Message::Sending => "Sending…",
+        Message::Failed => "Failed",
+        Message::ServerUnreachable => "Experiences server unreachable",
+        Message::PasswordRejected => "Experiences server rejected the password",
+        Message::DatabaseUnavailable => "Database unavailable",
+    }
+}
+
+/// Every message has a German text, so a missing translation fails to compile instead of falling back to English.
+fn german(message: Message) -> &'static str {
+    match message {
+        Message::Create => "Erstellen",
+        Message::Open => "Öffnen",
+        Message::Name => "Name",
+        Message::Icon => "Symbol",
+        Message::Color => "Farbe",
+        Message::Deleted => "Gelöscht",
+        Message::PossibleDuplicate => "Ähnliche Erlebnisse in der Nähe:",
+        Message::Retry => "Wiederholen",
+        Message::Discard => "Verwerfen",
+        Message::Template => "Vorlage",
+        Message::UnableToCreate => "Erlebnis konnte nicht erstellt werden",
+        Message::Menu => "Weitere Aktionen",
+        Message::MergeInto => "Zusammenführen mit…",
+        Message::MergeConfirm => "Erlebnisse zusammenführen? Die Medien werden verschoben und das erste wird gelöscht:",
+        Message::UnableToMerge => "Erlebnisse konnten nicht zusammengeführt werden",
+        Message::Parts => "Teile",
+        Message::PartOf => "Teil von",
+        Message::NoParent => "Keinem anderen Erlebnis",
+        Message::Series => "Serie",
+        Message::Previous => "Vorheriges",
+        Message::Next => "Nächstes",
+        Message::CreateNext => "Nächstes erstellen",
+        Message::Start => "Beginn",
+        Message::End => "Ende",
+        Message::Close => "Schließen",
+        Message::InvalidTime => "Das Ende muss nach dem Beginn liegen",
+        Message::UseBrowserTimezone => "Zeitzone dieses Browsers verwenden",
+        Message::UseConfiguredTimezone => "Zeitzone der Timeline verwenden",
+        Message::DropMedia => "Fotos oder Videos hier ablegen oder Dateien auswählen",
+        Message::UploadFailed => "Hochladen fehlgeschlagen",
+        Message::AddMedia => "Medien hinzufügen",
+        Message::BuiltFrom => "Aus {} Ereignissen erstellt",
+        Message::SuggestedExperience => "Vorgeschlagenes Erlebnis",
+        Message::SuggestionEvents => "{} Ereignisse",
+        Message::Accept => "Annehmen",
+        Message::Dismiss => "Verwerfen",
+        Message::UnableToDismiss => "Vorschlag konnte nicht verworfen werden",
+        Message::Statistics => "Statistiken",
+        Message::Loading => "Wird geladen…",
+        Message::RangeDuration => "{} Erlebnisse mit Dauer, zusammen",
+        Message::PerMonth => "Erlebnisse pro Monat",
+        Message::PerWeek => "Erlebnisse pro Woche",
+        Message::TopTags => "Häufigste Tags",
+        Message::TopNames => "Häufigste Namen",
+        Message::LongestGaps => "Längste Pausen",
+        Message::OneYearAgo => "Heute vor einem Jahr",
+        Message::YearsAgo => "Heute vor {} Jahren",
+        Message::OpenOriginal => "Original öffnen",
+        Message::WaitingForConnection => "Warte auf Verbindung",
+        Message::Sending => "Wird gesendet…",
+        Message::Failed => "Fehlgeschlagen",
+        Message::ServerUnreachable => "Erlebnis-Server nicht erreichbar",
+        Message::PasswordRejected => "Erlebnis-Server hat das Passwort abgelehnt",
+        Message::DatabaseUnavailable => "Datenbank nicht verfügbar",
+    }
+}
+
+fn format_date(millis: i64) -> String {
+    if !is_browser() {
+        return DateTime::from_timestamp_millis(millis)
+            .map(|v| v.with_timezone(&timezone::current()).format("%Y-%m-%d %H:%M").to_string())
+            .unwrap_or_default();
+    }
+    let options = Object::new();
+    let _ = Reflect::set(&options, &"dateStyle".into(), &"medium".into());
+    let _ = Reflect::set(&options, &"timeStyle".into(), &"short".into());
+    let _ = Reflect::set(&options, &"timeZone".into(), &timezone::current().name().into());
+    Date::new(&JsValue::from_f64(millis as f64))
+        .to_locale_string(Locale::current().tag(), &options)
+        .into()
+}
+
+/// Formats a timing with the browser locale's date conventions, in the time zone of the timeline.
+pub fn format_timing(timing: &Timing) -> String {
+    match timing {
+        Timing::Instant(v) => format_date(v.timestamp_millis()),
+        Timing::Range(v) => format!(
+            "{} – {}",
+            format_date(v.start.timestamp_millis()),
+            format_date(v.end.timestamp_millis())
+        ),
+    }
+}
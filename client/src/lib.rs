@@ -1,17 +1,85 @@
+#[cfg(test)]
+mod a11y_tests;
+mod create;
+mod health;
+mod i18n;
+mod live;
+mod media;
+mod memory;
+mod merge;
+mod mounted;
+mod offline;
+mod series;
+mod stats;
+mod suggest;
+mod suggestion;
+mod templates;
+mod timezone;
+mod upload;
+
 use { 
-    client_api::{external::url::{ParseError, Url}, plugin::{PluginData, PluginEventData, PluginTrait}, result::EventResult, style::{Style, StyledView}, types::{api::ExperiencesHostname, timing::Timing}}, leptos::{component, ev::MouseEvent, html::Input, spawn_local, use_context, view, window, Callback, Children, IntoView, MaybeSignal, View, create_signal}, serde::{Deserialize, Serialize}
+    crate::{create::CreateControl, health::{Health, HealthBanner, HealthReport}, i18n::{format_timing, t, t_format, Message}, live::{timing_start_ms, LiveExperiences, LiveState}, media::{MediaDrop, MediaStrip}, memory::MemoryCard, merge::MergeMenu, mounted::FirstMounted, offline::{PendingCard, PendingCreates}, series::{SeriesBadge, SeriesNavigation}, stats::StatsEntry, suggestion::SuggestionCard, templates::ExperienceTemplate, timezone::ClientConfig, upload::Uploads},
+    gloo_net::http::{Method, RequestBuilder, Response},
+    client_api::{external::url::{ParseError, Url}, plugin::{PluginData, PluginEventData, PluginTrait}, result::EventResult, style::Style, types::{api::{APIResult, ExperiencesHostname}, timing::Timing}}, leptos::{component, create_node_ref, create_rw_signal, ev::{KeyboardEvent, MouseEvent}, html::Div, spawn_local, use_context, view, window, Callback, Children, CollectView, For, IntoView, Show, SignalGet, MaybeSignal, RwSignal, SignalSet, SignalUpdate, SignalWith, View, create_signal}, serde::{de::DeserializeOwned, Deserialize, Serialize}
 };
 
-#[derive(Serialize, Deserialize)]
+const PLUGIN_API: &str = "/api/plugin/timeline_plugin_experience";
+
+#[derive(Serialize, Deserialize, Clone)]
 pub enum CompressedExperienceEvent {
-    Experience(String),
+    Experience {
+        id: String,
+        name: String,
+        color: Option<String>,
+        icon: Option<String>,
+        #[serde(default)]
+        children: Vec<ExperienceChild>,
+        #[serde(default)]
+        series: Option<SeriesBadge>,
+        #[serde(default)]
+        built_from: usize,
+    },
     Create(Timing),
+    Suggestion {
+        id: String,
+        name: String,
+        time: Timing,
+        event_count: usize,
+    },
+    Memory {
+        id: String,
+        name: String,
+        years: u32,
+        color: Option<String>,
+        icon: Option<String>,
+    },
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ExperienceChild {
+    pub id: String,
+    pub name: String,
+    pub time: Timing,
+    pub color: Option<String>,
+    pub icon: Option<String>,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Clone)]
 pub struct CreateExperienceRequest {
     pub name: String,
     pub time: Timing,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub idempotency_key: Option<String>,
+    #[serde(default)]
+    pub color: Option<String>,
+    #[serde(default)]
+    pub icon: Option<String>,
+    #[serde(default)]
+    pub parent_id: Option<String>,
+    #[serde(default)]
+    pub series_name: Option<String>,
 }
 
 pub fn relative_url(path: &str) -> Result<Url, ParseError> {
@@ -19,6 +87,59 @@ pub fn relative_url(path: &str) -> Result<Url, ParseError> {
     Url::parse(&experiences_host.0)?.join(path)
 }
 
+/// Sent with every mutating request. Pages of other sites can only set it after a CORS preflight, which the server never
+/// allows, so it proves the request came from this client even when the browser leaves out `Origin`. The server checks
+/// it against its own copy in `csrf.rs`, a test keeps the two equal.
+pub const REQUESTED_WITH: (&str, &str) = ("X-Requested-With", "timeline_plugin_experience");
+
+/// Request of this plugin. Every request starts here, so each one carries [`REQUESTED_WITH`].
+fn plugin_request(method: Method, url: &str) -> RequestBuilder {
+    RequestBuilder::new(url)
+        .method(method)
+        .header(REQUESTED_WITH.0, REQUESTED_WITH.1)
+}
+
+/// Reads the `APIResult` a route of this plugin answers with.
+async fn read_result<T: DeserializeOwned>(
+    response: Result<Response, gloo_net::Error>,
+) -> Result<T, String> {
+    response
+        .map_err(|e| e.to_string())?
+        .json::<APIResult<T>>()
+        .await
+        .map_err(|e| e.to_string())?
+        .map_err(|e| e.to_string())
+}
+
+/// `client_api::api::api_request` with [`REQUESTED_WITH`], which the server requires on mutating routes: a `POST` of a
+/// JSON body to `/api{path}`.
+pub async fn api_request<B: Serialize, T: DeserializeOwned>(path: &str, body: &B) -> Result<T, String> {
+    let request = plugin_request(Method::POST, &format!("/api{}", path))
+        .json(body)
+        .map_err(|e| e.to_string())?;
+    read_result(request.send().await).await
+}
+
+/// `GET` request against one of this plugin's server routes.
+pub async fn plugin_get<T: DeserializeOwned>(path: &str) -> Result<T, String> {
+    read_result(
+        plugin_request(Method::GET, &format!("{}{}", PLUGIN_API, path))
+            .send()
+            .await,
+    )
+    .await
+}
+
+pub(crate) fn open_experience(id: &str) {
+    window()
+        .location()
+        .set_href(
+            &relative_url(&format!("/experience/{}", id))
+                .unwrap()
+                .to_string(),
+        )
+        .unwrap();
+}
 
 #[component]
 pub fn Band(
@@ -26,63 +147,259 @@ pub fn Band(
     #[prop(into, default=create_signal("var(--accentColor3)".to_string()).0.into())]
     color: MaybeSignal<String>,
     #[prop(into, default=Callback::new(|_|{}))] click: Callback<MouseEvent, ()>,
+    /// Accessible name, for bands whose content alone does not describe the action.
+    #[prop(optional, into)]
+    label: Option<String>,
 ) -> impl IntoView {
+    let band = create_node_ref::<Div>();
+    // Enter and Space activate the band like a native button.
+    let keydown = move |e: KeyboardEvent| {
+        if e.key() == "Enter" || e.key() == " " {
+            e.prevent_default();
+            if let Some(band) = band.get() {
+                band.click();
+            }
+        }
+    };
+
     view! {
         <div
+            ref=band
             class="band"
             style="padding: var(--contentSpacing);box-sizing: border-box;color: var(--lightColor);width: 100%;display: flex;flex-direction: row;align-items: center;justify-content: center;position: relative;"
             style:background-color=color
             on:click=click
+            on:keydown=keydown
             role="button"
+            tabindex="0"
+            aria-label=label
         >
             {children()}
         </div>
     }
 }
 
+#[component]
+fn ExperienceCard(
+    id: String,
+    name: String,
+    color: Option<String>,
+    icon: Option<String>,
+    live: LiveExperiences,
+    /// Experiences that are part of this one.
+    #[prop(optional)]
+    parts: Vec<ExperienceChild>,
+    #[prop(optional)]
+    series: Option<SeriesBadge>,
+    /// Number of other plugins' events the experience was created from.
+    #[prop(optional)]
+    built_from: usize,
+) -> impl IntoView {
+    let uploads = Uploads::new();
+    let state = {
+        let id = id.clone();
+        move || live.states.with(|v| v.get(&id).cloned())
+    };
+
+    move || match state() {
+        Some(LiveState::Removed) => view! {
+            <Band color="var(--darkColor)">{t(Message::Deleted)}</Band>
+        }.into_view(),
+        state => {
+            let (name, title, color, icon, revision) = match state {
+                Some(LiveState::Changed { title, time, color, icon, revision }) => (
+                    title.clone(),
+                    Some(format!("{} · {}", title, format_timing(&time))),
+                    color,
+                    icon,
+                    revision,
+                ),
+                _ => (name.clone(), None, color.clone(), icon.clone(), 0),
+            };
+            // Overriding the theme variables on the card recolors everything inside it, including the bands.
+            let accent = color
+                .map(|color| format!("--accentColor1: {color};--accentColor3: {color};"))
+                .unwrap_or_default();
+            let title = match (&icon, title) {
+                (Some(icon), Some(title)) => Some(format!("{} {}", icon, title)),
+                (Some(icon), None) => Some(icon.clone()),
+                (None, title) => title,
+            };
+            let id = id.clone();
+            let open_label = format!("{} {}", t(Message::Open), name);
+            let menu = view! { <MergeMenu id=id.clone() name=name.clone() live=live /> };
+            let series = series.clone().map(|series| view! { <SeriesNavigation series=series /> });
+            let parts = (!parts.is_empty()).then(|| view! { <ChildList parts=parts.clone() /> });
+            let drop_id = id.clone();
+            let strip_id = id.clone();
+            view! {
+                <div style=format!("display: flex; flex-direction: column;background-color: var(--accentColor1);{}", accent)>
+                    <MediaDrop id=drop_id uploads=uploads>
+                    {title.map(|title| view! {
+                        <div style="padding: var(--contentSpacing);color: var(--lightColor);">{title}</div>
+                    })}
+                    <img
+                        style="width: 100%"
+                        src=relative_url(&format!("/api/experience/{}/cover/big?v={}", id, revision))
+                            .unwrap()
+                            .to_string()
+                        alt=name
+                    />
+                    <MediaStrip id=strip_id revision=revision />
+                    {(built_from > 0).then(|| view! {
+                        <div style="padding: var(--contentSpacing);color: var(--lightColor);font-size: 0.8em;">
+                            {t_format(Message::BuiltFrom, &built_from.to_string())}
+                        </div>
+                    })}
+                    <Band label=open_label click=Callback::new(move |_| open_experience(&id))>
+                        {t(Message::Open)}
+                    </Band>
+                    {series}
+                    {parts}
+                    {menu}
+                    </MediaDrop>
+                </div>
+            }.into_view()
+        }
+    }
+}
+
+/// Expandable list of the experiences that are part of a card's experience.
+#[component]
+fn ChildList(parts: Vec<ExperienceChild>) -> impl IntoView {
+    let expanded = create_rw_signal(false);
+    let toggle = format!("{} ({})", t(Message::Parts), parts.len());
+
+    view! {
+        <Band
+            color="var(--accentColor2)"
+            click=Callback::new(move |_| expanded.update(|v| *v = !*v))
+        >
+            {move || format!("{} {}", toggle, if expanded.get() { "▴" } else { "▾" })}
+        </Band>
+        <Show when=move || expanded.get()>
+            <div style="display: flex; flex-direction: column;">
+                {parts
+                    .iter()
+                    .map(|child| {
+                        let id = child.id.clone();
+                        let title = match &child.icon {
+                            Some(icon) => format!("{} {} · {}", icon, child.name, format_timing(&child.time)),
+                            None => format!("{} · {}", child.name, format_timing(&child.time)),
+                        };
+                        view! {
+                            <Band
+                                color=child.color.clone().unwrap_or("var(--accentColor3)".to_string())
+                                click=Callback::new(move |_| open_experience(&id))
+                            >
+                                {title}
+                            </Band>
+                        }
+                    })
+                    .collect_view()}
+            </div>
+        </Show>
+    }
+}
 
 pub struct Plugin {
     #[allow(unused)]
     plugin_data: PluginData,
+    health: Health,
+    stats: FirstMounted,
+    live: LiveExperiences,
+    pending: PendingCreates,
+    templates: RwSignal<Vec<ExperienceTemplate>>,
 }
 
 impl PluginTrait for Plugin {
     async fn new(data: PluginData) -> Self
         where
             Self: Sized {
+            let health = Health::new();
+            spawn_local(async move {
+                if let Ok(report) = plugin_get::<HealthReport>("/health").await {
+                    health.report.set(Some(report));
+                }
+            });
+
+            // Loaded before any card renders, so every time is shown in the right time zone.
+            if let Ok(config) = plugin_get::<ClientConfig>("/config").await {
+                timezone::set_configured(&config.timezone);
+            }
+
+            let live = LiveExperiences::new();
+            live.subscribe();
+            let pending = PendingCreates::load();
+            pending.listen(live);
+            let templates = create_rw_signal(Vec::new());
+            spawn_local(async move {
+                if let Ok(v) = plugin_get::<Vec<ExperienceTemplate>>("/templates").await {
+                    templates.set(v);
+                }
+            });
+
             Plugin {
-                plugin_data: data
+                plugin_data: data,
+                health,
+                stats: FirstMounted::new(),
+                live,
+                pending,
+                templates,
             }
     }
 
     fn get_component(&self, data: PluginEventData) -> EventResult<Box<dyn FnOnce() -> leptos::View>> {
         let data = data.get_data::<CompressedExperienceEvent>()?;
+        let health = self.health;
+        let stats = self.stats;
+        let live = self.live;
+        let pending = self.pending;
+        let templates = self.templates;
+        if let CompressedExperienceEvent::Experience { id, name, .. } = &data {
+            live.known.update(|v| {
+                v.insert(id.clone(), name.clone());
+            });
+        }
         Ok(Box::new(move || -> View {
             match data {
-                CompressedExperienceEvent::Experience(id) => {
+                CompressedExperienceEvent::Experience { id, name, color, icon, children, series, built_from } => {
                     view! {
-                        <div style="display: flex; flex-direction: column;">
-                            <img
-                                style="width: 100%"
-                                src=relative_url(&format!("/api/experience/{}/cover/big", id))
-                                    .unwrap()
-                                    .to_string()
-                            />
-                            <Band on:click=Callback::new(move |_| {
-                                window()
-                                    .location()
-                                    .set_href(
-                                        &relative_url(&format!("/experience/{}", id))
-                                            .unwrap()
-                                            .to_string(),
-                                    )
-                                    .unwrap();
-                            })>Open</Band>
-                        </div>
+                        <ExperienceCard id=id name=name color=color icon=icon live=live parts=children series=series built_from=built_from />
+                    }.into_view()
+                }
+                CompressedExperienceEvent::Suggestion { id, name, time, event_count } => {
+                    view! {
+                        <SuggestionCard id=id name=name time=time event_count=event_count />
+                    }.into_view()
+                }
+                CompressedExperienceEvent::Memory { id, name, years, color, icon } => {
+                    view! {
+                        <MemoryCard id=id name=name years=years color=color icon=icon />
                     }.into_view()
                 }
                 CompressedExperienceEvent::Create(timing) => {
-                    let name_ref = leptos::create_node_ref::<Input>();
+                    // Experiences created after the timeline was loaded are shown in the slot they start in.
+                    let slot_start = timing_start_ms(&timing);
+                    let inserted = move || {
+                        let known = live.known.with(|v| v.clone());
+                        live.states.with(|states| {
+                            states
+                                .iter()
+                                .filter_map(|(id, state)| match state {
+                                    LiveState::Changed { title, time, .. }
+                                        if !known.contains_key(id)
+                                            && (slot_start..slot_start + 3_600_000)
+                                                .contains(&timing_start_ms(time)) =>
+                                    {
+                                        Some((id.clone(), title.clone()))
+                                    }
+                                    _ => None,
+                                })
+                                .collect::<Vec<_>>()
+                        })
+                    };
+                    let queued = pending.in_slot(slot_start, slot_start + 3_600_000);
 
                     view! {
                         <style>
@@ -100,35 +417,42 @@ impl PluginTrait for Plugin {
                             }
                             .name_input:focus{
                              outline: none;
+                            }
+                            .name_input:focus-visible{
+                             outline: 2px solid var(--lightColor);
+                             outline-offset: -2px;
+                            }
+                            .visually_hidden {
+                             position: absolute;
+                             width: 1px;
+                             height: 1px;
+                             overflow: hidden;
+                             clip: rect(0 0 0 0);
+                             white-space: nowrap;
+                            }
+                            .band:focus-visible {
+                             outline: 2px solid var(--lightColor);
+                             outline-offset: -2px;
                             }"
                         </style>
-                        <StyledView>
-                            <input ref=name_ref class="name_input" placeholder="Name" />
-                            <Band click=Callback::new(move |_| {
-                                let timing = timing.clone();
-                                spawn_local(async move {
-                                    match client_api::api::api_request(
-                                            "/plugin/timeline_plugin_experience/create",
-                                            &CreateExperienceRequest {
-                                                name: name_ref.get().unwrap().value(),
-                                                time: timing.clone(),
-                                            },
-                                        )
-                                        .await
-                                    {
-                                        Ok(()) => {
-                                            let _ = window().location().reload();
-                                        }
-                                        Err(e) => {
-                                            let _ = window()
-                                                .alert_with_message(
-                                                    &format!("Unable to create Experience: {}", e),
-                                                );
-                                        }
-                                    }
-                                })
-                            })>Create</Band>
-                        </StyledView>
+                        <HealthBanner health=health />
+                        <StatsEntry entries=stats />
+                        <For each=inserted key=|(id, _)| id.clone() let:experience>
+                            <ExperienceCard id=experience.0 name=experience.1 color=None icon=None live=live />
+                        </For>
+                        <For
+                            each=queued
+                            key=|item| (item.key.clone(), serde_json::to_string(&item.status).unwrap())
+                            let:item
+                        >
+                            <PendingCard item=item pending=pending live=live />
+                        </For>
+                        {timezone::slots_in(&timing)
+                            .into_iter()
+                            .map(|timing| view! {
+                                <CreateControl timing=timing live=live pending=pending templates=templates />
+                            })
+                            .collect_view()}
                     }.into_view()
                 }
             }
@@ -138,4 +462,19 @@ impl PluginTrait for Plugin {
     fn get_style(&self) -> Style {
         Style::Acc1
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The server defines the header on its own, so the two copies are compared here.
+    #[test]
+    fn requested_with_matches_the_server() {
+        let server = include_str!("../../server/src/csrf.rs");
+        let expected = format!(
+            "pub const REQUESTED_WITH: (&str, &str) = ({:?}, {:?});",
+            REQUESTED_WITH.0, REQUESTED_WITH.1
+        );
+        assert!(server.contains(&expected), "{}", expected);
+    }
+}
@@ -0,0 +1,207 @@
+use {
+    crate::{CompressedExperienceEvent, PLUGIN_API},
+    client_api::types::timing::Timing,
+    leptos::{set_timeout, RwSignal, SignalGet, SignalUpdate, SignalWith},
+    serde::Deserialize,
+    std::{
+        cell::{Cell, RefCell},
+        collections::HashMap,
+        rc::Rc,
+        time::Duration,
+    },
+    wasm_bindgen::{closure::Closure, JsCast},
+    web_sys::{EventSource, MessageEvent},
+};
+
+const MAX_BACKOFF_MS: u64 = 30_000;
+
+#[derive(Deserialize, Clone)]
+pub enum LiveUpdate {
+    Changed {
+        title: String,
+        time: Timing,
+        data: CompressedExperienceEvent,
+    },
+    Removed {
+        id: String,
+    },
+}
+
+#[derive(Clone)]
+pub enum LiveState {
+    /// `revision` increases with every change and is used to refetch the cover.
+    Changed {
+        title: String,
+        time: Timing,
+        color: Option<String>,
+        icon: Option<String>,
+        revision: u32,
+    },
+    Removed,
+}
+
+/// Experiences changed since the timeline was loaded, shared between all cards of the plugin.
+#[derive(Clone, Copy)]
+pub struct LiveExperiences {
+    pub states: RwSignal<HashMap<String, LiveState>>,
+    /// Names of the experiences that already have their own card, so they are not inserted a second time.
+    pub known: RwSignal<HashMap<String, String>>,
+}
+
+impl LiveExperiences {
+    pub fn new() -> Self {
+        LiveExperiences {
+            states: RwSignal::new(HashMap::new()),
+            known: RwSignal::new(HashMap::new()),
+        }
+    }
+
+    pub fn apply(&self, update: LiveUpdate) {
+        self.states.update(|states| match update {
+            LiveUpdate::Changed {
+                title,
+                time,
+                data:
+                    CompressedExperienceEvent::Experience {
+                        id, color, icon, ..
+                    },
+            } => {
+                let revision = match states.get(&id) {
+                    Some(LiveState::Changed { revision, .. }) => revision + 1,
+                    _ => 1,
+                };
+                states.insert(
+                    id,
+                    LiveState::Changed {
+                        title,
+                        time,
+                        color,
+                        icon,
+                        revision,
+                    },
+                );
+            }
+            LiveUpdate::Changed { .. } => {}
+            LiveUpdate::Removed { id } => {
+                states.insert(id, LiveState::Removed);
+            }
+        })
+    }
+
+    /// Id and current name of every experience on the timeline that was not deleted.
+    pub fn experiences(&self) -> Vec<(String, String)> {
+        let mut result = self.known.get();
+        self.states.with(|states| {
+            for (id, state) in states {
+                match state {
+                    LiveState::Changed { title, .. } => {
+                        result.insert(id.clone(), title.clone());
+                    }
+                    LiveState::Removed => {
+                        result.remove(id);
+                    }
+                }
+            }
+        });
+        let mut result = result.into_iter().collect::<Vec<_>>();
+        result.sort_by(|a, b| a.1.cmp(&b.1));
+        result
+    }
+
+    /// Opens the `/live` event stream and keeps it open, reconnecting with exponential backoff.
+    pub fn subscribe(self) {
+        connect(Rc::new(Stream {
+            live: self,
+            attempt: Cell::new(0),
+            connection: RefCell::new(None),
+        }));
+    }
+}
+
+impl Default for LiveExperiences {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Subscription to `/live` that outlives the single connections.
+struct Stream {
+    live: LiveExperiences,
+    attempt: Cell<u32>,
+    connection: RefCell<Option<Connection>>,
+}
+
+/// Open event source with its handlers. Dropping it detaches and frees the handlers.
+struct Connection {
+    source: EventSource,
+    _on_open: Closure<dyn FnMut()>,
+    _on_message: Closure<dyn FnMut(MessageEvent)>,
+    _on_error: Closure<dyn FnMut()>,
+}
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        self.source.set_onopen(None);
+        self.source.set_onmessage(None);
+        self.source.set_onerror(None);
+        self.source.close();
+    }
+}
+
+fn connect(stream: Rc<Stream>) {
+    let Ok(source) = EventSource::new(&format!("{}/live", PLUGIN_API)) else {
+        reconnect(stream);
+        return;
+    };
+
+    let on_open = Closure::<dyn FnMut()>::new({
+        let stream = stream.clone();
+        move || stream.attempt.set(0)
+    });
+    let live = stream.live;
+    let on_message = Closure::<dyn FnMut(MessageEvent)>::new(move |event: MessageEvent| {
+        if let Some(update) = event
+            .data()
+            .as_string()
+            .and_then(|v| serde_json::from_str::<LiveUpdate>(&v).ok())
+        {
+            live.apply(update);
+        }
+    });
+    let on_error = Closure::<dyn FnMut()>::new({
+        let source = source.clone();
+        let stream = stream.clone();
+        move || {
+            // The browser would retry on its own without any backoff.
+            source.close();
+            reconnect(stream.clone());
+        }
+    });
+
+    source.set_onopen(Some(on_open.as_ref().unchecked_ref()));
+    source.set_onmessage(Some(on_message.as_ref().unchecked_ref()));
+    source.set_onerror(Some(on_error.as_ref().unchecked_ref()));
+    // The handlers keep the stream alive for as long as the page, and only the current connection keeps its handlers.
+    // The failed one is dropped here, never while one of its handlers runs, as reconnects go through a timeout.
+    stream.connection.replace(Some(Connection {
+        source,
+        _on_open: on_open,
+        _on_message: on_message,
+        _on_error: on_error,
+    }));
+}
+
+fn reconnect(stream: Rc<Stream>) {
+    let n = stream.attempt.get();
+    stream.attempt.set(n + 1);
+    let delay = (1000u64 << n.min(5)).min(MAX_BACKOFF_MS);
+    set_timeout(move || connect(stream), Duration::from_millis(delay));
+}
+
+/// Start of a timing in milliseconds since the epoch.
+pub fn timing_start_ms(timing: &Timing) -> i64 {
+    match timing {
+        Timing::Instant(v) => v.timestamp_millis(),
+        Timing::Range(v) => v.start.timestamp_millis(),
+    }
+}
@@ -0,0 +1,126 @@
+use {
+    crate::{
+        i18n::{t, Message},
+        plugin_get, relative_url,
+        upload::{files, Uploads},
+        Band,
+    },
+    js_sys::encode_uri_component,
+    leptos::{
+        component, create_local_resource, create_node_ref, create_rw_signal, ev::DragEvent,
+        html::Input, view, window, Callback, CollectView, IntoView, SignalGet, SignalSet,
+    },
+    web_sys::File,
+};
+
+/// Thumbnails of the media of an experience. `revision` is part of the image urls, so they are refetched after a change.
+#[component]
+pub fn MediaStrip(id: String, revision: u32) -> impl IntoView {
+    let media = create_local_resource(
+        {
+            let id = id.clone();
+            move || id.clone()
+        },
+        |id| async move { plugin_get::<Vec<String>>(&format!("/experience/{}/media", id)).await },
+    );
+
+    move || {
+        let media = match media.get() {
+            Some(Ok(v)) if !v.is_empty() => v,
+            _ => return None,
+        };
+        Some(view! {
+            <div style="display: flex;flex-direction: row;overflow-x: auto;gap: 2px;">
+                {media
+                    .into_iter()
+                    .map(|media| view! {
+                        <img
+                            style="height: 4em;flex: none;"
+                            loading="lazy"
+                            alt=""
+                            src=relative_url(&format!(
+                                "/api/experience/{}/media/{}/thumbnail?v={}",
+                                id, media, revision
+                            ))
+                            .unwrap()
+                            .to_string()
+                        />
+                    })
+                    .collect_view()}
+            </div>
+        })
+    }
+}
+
+/// Drop zone covering a card plus an "Add media" action. Uploads outlive a rerender of the card, so their state is passed in.
+#[component]
+pub fn MediaDrop(id: String, uploads: Uploads, children: leptos::Children) -> impl IntoView {
+    let dragging = create_rw_signal(false);
+    let picker = create_node_ref::<Input>();
+
+    let upload = {
+        let id = id.clone();
+        move |files: Vec<File>| {
+            for file in files {
+                let path = format!(
+                    "/experience/{}/media?filename={}",
+                    id,
+                    encode_uri_component(&file.name())
+                );
+                // The cover refreshes through the live update the server sends afterwards.
+                uploads.send::<()>(path, file, |result| {
+                    if let Err(e) = result {
+                        let _ = window()
+                            .alert_with_message(&format!("{}: {}", t(Message::UploadFailed), e));
+                    }
+                });
+            }
+        }
+    };
+    let drop_upload = upload.clone();
+
+    view! {
+        <div
+            style="display: flex; flex-direction: column;"
+            style:outline=move || if dragging.get() { "2px dashed var(--lightColor)" } else { "none" }
+            style:outline-offset="-2px"
+            on:dragover=move |e: DragEvent| {
+                e.prevent_default();
+                dragging.set(true);
+            }
+            on:dragleave=move |_| dragging.set(false)
+            on:drop=move |e: DragEvent| {
+                e.prevent_default();
+                dragging.set(false);
+                drop_upload(files(e.data_transfer().and_then(|v| v.files())));
+            }
+        >
+            {children()}
+            <input
+                ref=picker
+                type="file"
+                class="visually_hidden"
+                accept="image/*,video/*"
+                multiple
+                tabindex="-1"
+                on:change=move |_| {
+                    if let Some(input) = picker.get() {
+                        upload(files(input.files()));
+                        input.set_value("");
+                    }
+                }
+            />
+            <Band
+                color="var(--accentColor2)"
+                click=Callback::new(move |_| {
+                    if let Some(input) = picker.get() {
+                        input.click();
+                    }
+                })
+            >
+                {t(Message::AddMedia)}
+            </Band>
+            {uploads.view()}
+        </div>
+    }
+}
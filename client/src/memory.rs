@@ -0,0 +1,53 @@
+use {
+    crate::{
+        i18n::{t, t_format, Message},
+        open_experience, relative_url, Band,
+    },
+    leptos::{component, view, Callback, IntoView},
+};
+
+/// "On this day" card for an experience from a previous year. Faded and smaller than real experiences, linking to the original.
+#[component]
+pub fn MemoryCard(
+    id: String,
+    name: String,
+    years: u32,
+    color: Option<String>,
+    icon: Option<String>,
+) -> impl IntoView {
+    let heading = if years == 1 {
+        t(Message::OneYearAgo).to_string()
+    } else {
+        t_format(Message::YearsAgo, &years.to_string())
+    };
+    let title = match icon {
+        Some(icon) => format!("{} {}", icon, name),
+        None => name.clone(),
+    };
+    let accent = color
+        .map(|color| format!("--accentColor1: {color};--accentColor3: {color};"))
+        .unwrap_or_default();
+    let cover = relative_url(&format!("/api/experience/{}/cover/big", id))
+        .unwrap()
+        .to_string();
+    let open_label = format!("{} {}", t(Message::OpenOriginal), name);
+
+    view! {
+        <div
+            style=format!("display: flex; flex-direction: column;background-color: var(--accentColor1);opacity: 0.75;border-left: 4px solid var(--lightColor);{}", accent)
+            role="group"
+            aria-label=heading.clone()
+        >
+            <div style="padding: var(--contentSpacing);color: var(--lightColor);font-size: 0.8em;">
+                {format!("🕰 {}", heading)}
+            </div>
+            <div style="display: flex; flex-direction: row; align-items: center;">
+                <img style="width: 30%;" src=cover alt=name.clone() />
+                <div style="flex: 1; padding: var(--contentSpacing);color: var(--lightColor);">{title}</div>
+            </div>
+            <Band label=open_label click=Callback::new(move |_| open_experience(&id))>
+                {t(Message::OpenOriginal)}
+            </Band>
+        </div>
+    }
+}
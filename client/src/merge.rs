@@ -0,0 +1,101 @@
+use {
+    crate::{
+        api_request,
+        i18n::{t, Message},
+        live::{LiveExperiences, LiveUpdate},
+        Band,
+    },
+    leptos::{
+        component, create_rw_signal, spawn_local, view, window, Callback, For, IntoView, Show,
+        SignalGet, SignalSet, SignalUpdate,
+    },
+    serde::Serialize,
+};
+
+#[derive(Serialize)]
+struct MergeRequest {
+    source: String,
+    target: String,
+}
+
+/// Card menu offering to merge this experience into another one on the timeline.
+#[component]
+pub fn MergeMenu(id: String, name: String, live: LiveExperiences) -> impl IntoView {
+    let open = create_rw_signal(false);
+    let source = id.clone();
+    let targets = move || {
+        live.experiences()
+            .into_iter()
+            .filter(|(v, _)| *v != source)
+            .collect::<Vec<_>>()
+    };
+
+    view! {
+        <Band
+            color="var(--accentColor2)"
+            label=t(Message::Menu)
+            click=Callback::new(move |_| open.update(|v| *v = !*v))
+        >
+            "⋯"
+        </Band>
+        <Show when=move || open.get()>
+            <div role="menu" style="display: flex; flex-direction: column;">
+                <div style="padding: var(--contentSpacing);color: var(--lightColor);">
+                    {t(Message::MergeInto)}
+                </div>
+                <For each=targets.clone() key=|(id, name)| (id.clone(), name.clone()) let:target>
+                    {
+                        let source = id.clone();
+                        let source_name = name.clone();
+                        let (target_id, target_name) = target;
+                        view! {
+                            <Band
+                                color="var(--accentColor1)"
+                                click=Callback::new({
+                                    let target_name = target_name.clone();
+                                    move |_| {
+                                        let confirmed = window()
+                                            .confirm_with_message(&format!(
+                                                "{} „{}“ → „{}“?",
+                                                t(Message::MergeConfirm),
+                                                source_name,
+                                                target_name
+                                            ))
+                                            .unwrap_or(false);
+                                        if !confirmed {
+                                            return;
+                                        }
+                                        open.set(false);
+                                        let request = MergeRequest {
+                                            source: source.clone(),
+                                            target: target_id.clone(),
+                                        };
+                                        spawn_local(async move {
+                                            let result: Result<(), _> = api_request(
+                                                "/plugin/timeline_plugin_experience/merge",
+                                                &request,
+                                            )
+                                            .await;
+                                            match result {
+                                                Ok(()) => live.apply(LiveUpdate::Removed { id: request.source }),
+                                                Err(e) => {
+                                                    let _ = window().alert_with_message(&format!(
+                                                        "{}: {}",
+                                                        t(Message::UnableToMerge),
+                                                        e
+                                                    ));
+                                                }
+                                            }
+                                        });
+                                    }
+                                })
+                            >
+                                {target_name}
+                            </Band>
+                        }
+                    }
+                </For>
+            </div>
+        </Show>
+    }
+}
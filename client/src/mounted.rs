@@ -0,0 +1,35 @@
+use {
+    leptos::{create_rw_signal, on_cleanup, RwSignal, Signal, SignalUpdate, SignalWith},
+    std::sync::atomic::{AtomicU64, Ordering},
+};
+
+static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+
+/// Mounted copies of a view every slot of the timeline renders, in the order they were rendered. Only the first one is
+/// shown, and the next one takes over when it scrolls out.
+#[derive(Clone, Copy)]
+pub struct FirstMounted {
+    ids: RwSignal<Vec<u64>>,
+}
+
+impl FirstMounted {
+    pub fn new() -> Self {
+        FirstMounted {
+            ids: create_rw_signal(Vec::new()),
+        }
+    }
+
+    /// Registers the copy being rendered until it is cleaned up. The signal tells whether it is the one shown.
+    pub fn register(self) -> Signal<bool> {
+        let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+        self.ids.update(|v| v.push(id));
+        on_cleanup(move || self.ids.update(|v| v.retain(|other| *other != id)));
+        Signal::derive(move || self.ids.with(|v| v.first() == Some(&id)))
+    }
+}
+
+impl Default for FirstMounted {
+    fn default() -> Self {
+        Self::new()
+    }
+}
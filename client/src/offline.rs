@@ -0,0 +1,205 @@
+use {
+    crate::{
+        api_request,
+        i18n::{format_timing, t, Message},
+        live::{timing_start_ms, LiveExperiences, LiveUpdate},
+        Band, CompressedExperienceEvent, CreateExperienceRequest,
+    },
+    leptos::{
+        component, spawn_local, view, window, window_event_listener_untyped, Callback, IntoView,
+        RwSignal, SignalGet, SignalGetUntracked, SignalUpdate,
+    },
+    serde::{Deserialize, Serialize},
+};
+
+const STORAGE_KEY: &str = "timeline_plugin_experience_pending";
+
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+pub enum PendingStatus {
+    /// Waiting for connectivity.
+    Queued,
+    Sending,
+    Failed(String),
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct PendingCreate {
+    /// Sent as idempotency key, so a replayed request never creates a second experience.
+    pub key: String,
+    #[serde(flatten)]
+    pub request: CreateExperienceRequest,
+    pub status: PendingStatus,
+}
+
+/// Create requests that did not reach the server yet. Persisted in local storage so they survive reloads.
+#[derive(Clone, Copy)]
+pub struct PendingCreates {
+    pub items: RwSignal<Vec<PendingCreate>>,
+}
+
+impl PendingCreates {
+    pub fn load() -> Self {
+        let items = window()
+            .local_storage()
+            .ok()
+            .flatten()
+            .and_then(|storage| storage.get_item(STORAGE_KEY).ok().flatten())
+            .and_then(|v| serde_json::from_str::<Vec<PendingCreate>>(&v).ok())
+            .unwrap_or_default()
+            .into_iter()
+            .map(|mut v| {
+                // The page was closed while the request was in flight.
+                if v.status == PendingStatus::Sending {
+                    v.status = PendingStatus::Queued;
+                }
+                v
+            })
+            .collect();
+        PendingCreates {
+            items: RwSignal::new(items),
+        }
+    }
+
+    fn persist(&self) {
+        if let Some(storage) = window().local_storage().ok().flatten() {
+            let _ = storage.set_item(
+                STORAGE_KEY,
+                &serde_json::to_string(&self.items.get_untracked()).unwrap(),
+            );
+        }
+    }
+
+    fn set_status(&self, key: &str, status: PendingStatus) {
+        self.items.update(|items| {
+            if let Some(item) = items.iter_mut().find(|v| v.key == key) {
+                item.status = status;
+            }
+        });
+        self.persist();
+    }
+
+    pub fn remove(&self, key: &str) {
+        self.items.update(|items| items.retain(|v| v.key != key));
+        self.persist();
+    }
+
+    /// Queues a new request and sends it right away if the browser is online.
+    pub fn create(self, mut request: CreateExperienceRequest, live: LiveExperiences) {
+        let key = window()
+            .crypto()
+            .map(|v| v.random_uuid())
+            .unwrap_or_else(|_| format!("{}-{}", js_sys::Date::now(), js_sys::Math::random()));
+        request.idempotency_key = Some(key.clone());
+        self.items.update(|items| {
+            items.push(PendingCreate {
+                key: key.clone(),
+                request,
+                status: PendingStatus::Queued,
+            })
+        });
+        self.persist();
+        self.send(key, live);
+    }
+
+    pub fn send(self, key: String, live: LiveExperiences) {
+        if !window().navigator().on_line() {
+            self.set_status(&key, PendingStatus::Queued);
+            return;
+        }
+        let Some(item) = self.items.get_untracked().into_iter().find(|v| v.key == key) else {
+            return;
+        };
+        if item.status == PendingStatus::Sending {
+            return;
+        }
+        self.set_status(&key, PendingStatus::Sending);
+        spawn_local(async move {
+            let mut request = item.request;
+            request.idempotency_key = Some(item.key);
+            let result: Result<String, _> = api_request(
+                "/plugin/timeline_plugin_experience/create",
+                &request,
+            )
+            .await;
+            match result {
+                Ok(id) => {
+                    self.remove(&key);
+                    live.apply(LiveUpdate::Changed {
+                        title: request.name.clone(),
+                        time: request.time,
+                        data: CompressedExperienceEvent::Experience {
+                            id,
+                            name: request.name,
+                            color: request.color,
+                            icon: request.icon,
+                            children: Vec::new(),
+                            series: None,
+                            built_from: 0,
+                        },
+                    });
+                }
+                Err(e) => self.set_status(&key, PendingStatus::Failed(e.to_string())),
+            }
+        });
+    }
+
+    /// Sends every request that is not in flight.
+    pub fn replay(self, live: LiveExperiences) {
+        for item in self.items.get_untracked() {
+            if item.status != PendingStatus::Sending {
+                self.send(item.key, live);
+            }
+        }
+    }
+
+    /// Replays the queue now and whenever the browser comes back online.
+    pub fn listen(self, live: LiveExperiences) {
+        self.replay(live);
+        let _ = window_event_listener_untyped("online", move |_| self.replay(live));
+    }
+
+    /// Requests whose timing starts inside `[start_ms, end_ms)`.
+    pub fn in_slot(&self, start_ms: i64, end_ms: i64) -> impl Fn() -> Vec<PendingCreate> {
+        let items = self.items;
+        move || {
+            items
+                .get()
+                .into_iter()
+                .filter(|v| (start_ms..end_ms).contains(&timing_start_ms(&v.request.time)))
+                .collect()
+        }
+    }
+}
+
+#[component]
+pub fn PendingCard(
+    item: PendingCreate,
+    pending: PendingCreates,
+    live: LiveExperiences,
+) -> impl IntoView {
+    let status = match &item.status {
+        PendingStatus::Queued => t(Message::WaitingForConnection).to_string(),
+        PendingStatus::Sending => t(Message::Sending).to_string(),
+        PendingStatus::Failed(e) => format!("{}: {}", t(Message::Failed), e),
+    };
+    let retry_key = item.key.clone();
+    let discard_key = item.key.clone();
+
+    view! {
+        <div style="display: flex; flex-direction: column; opacity: 0.7;">
+            <div style="padding: var(--contentSpacing);color: var(--lightColor);">
+                {item.request.name}
+                <div style="font-size: 0.8em;">{format_timing(&item.request.time)}</div>
+                <div style="font-size: 0.8em;">{status}</div>
+            </div>
+            <div style="display: flex; flex-direction: row;">
+                <Band click=Callback::new(move |_| pending.send(retry_key.clone(), live))>
+                    {t(Message::Retry)}
+                </Band>
+                <Band click=Callback::new(move |_| pending.remove(&discard_key))>
+                    {t(Message::Discard)}
+                </Band>
+            </div>
+        </div>
+    }
+}
@@ -0,0 +1,96 @@
+use {
+    crate::{
+        api_request,
+        i18n::{t, Message},
+        open_experience, Band,
+    },
+    leptos::{component, spawn_local, view, window, Callback, IntoView},
+    serde::{Deserialize, Serialize},
+};
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct SeriesBadge {
+    pub id: String,
+    pub name: String,
+    pub number: u32,
+    pub previous: Option<String>,
+    pub next: Option<String>,
+}
+
+#[derive(Serialize)]
+struct NextOccurrenceRequest {
+    series_id: String,
+}
+
+/// Name and number of the series an experience belongs to, with links to its neighbors.
+/// The last occurrence offers to create the next one instead.
+#[component]
+pub fn SeriesNavigation(series: SeriesBadge) -> impl IntoView {
+    let title = format!("{} #{}", series.name, series.number);
+    let previous = series.previous.clone().map(|id| {
+        view! {
+            <Band
+                color="var(--accentColor2)"
+                label=t(Message::Previous)
+                click=Callback::new(move |_| open_experience(&id))
+            >
+                "‹"
+            </Band>
+        }
+    });
+    let next = match series.next.clone() {
+        Some(id) => view! {
+            <Band
+                color="var(--accentColor2)"
+                label=t(Message::Next)
+                click=Callback::new(move |_| open_experience(&id))
+            >
+                "›"
+            </Band>
+        },
+        None => {
+            let series_id = series.id.clone();
+            view! {
+                <Band
+                    color="var(--accentColor2)"
+                    label=t(Message::CreateNext)
+                    click=Callback::new(move |_| {
+                        let request = NextOccurrenceRequest {
+                            series_id: series_id.clone(),
+                        };
+                        // The new occurrence appears through the live updates.
+                        spawn_local(async move {
+                            let result: Result<String, _> = api_request(
+                                "/plugin/timeline_plugin_experience/series/next",
+                                &request,
+                            )
+                            .await;
+                            if let Err(e) = result {
+                                let _ = window().alert_with_message(&format!(
+                                    "{}: {}",
+                                    t(Message::UnableToCreate),
+                                    e
+                                ));
+                            }
+                        });
+                    })
+                >
+                    "+"
+                </Band>
+            }
+        }
+    };
+
+    view! {
+        <div style="display: flex; flex-direction: row; align-items: stretch;">
+            <div style="width: 3em; display: flex;">{previous}</div>
+            <div
+                style="flex: 1; padding: var(--contentSpacing); color: var(--lightColor); text-align: center;"
+                aria-label=t(Message::Series)
+            >
+                {title}
+            </div>
+            <div style="width: 3em; display: flex;">{next}</div>
+        </div>
+    }
+}
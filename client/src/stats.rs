@@ -0,0 +1,164 @@
+use {
+    crate::{
+        i18n::{format_timing, t, t_format, Message},
+        mounted::FirstMounted,
+        plugin_get, Band,
+    },
+    client_api::{
+        style::StyledView,
+        types::{
+            external::chrono::{DateTime, TimeDelta, Utc},
+            timing::{TimeRange, Timing},
+        },
+    },
+    js_sys::encode_uri_component,
+    leptos::{component, create_local_resource, create_rw_signal, view, Callback, CollectView, IntoView, Show, SignalGet, SignalUpdate},
+    serde::Deserialize,
+};
+
+#[derive(Deserialize, Clone)]
+pub struct PeriodCount {
+    pub period: String,
+    pub count: u64,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct Gap {
+    pub from: DateTime<Utc>,
+    pub to: DateTime<Utc>,
+    pub minutes: i64,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct ValueCount {
+    pub value: String,
+    pub count: u64,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct Stats {
+    pub weeks: Vec<PeriodCount>,
+    pub months: Vec<PeriodCount>,
+    pub range_experiences: u64,
+    pub range_minutes: i64,
+    pub longest_gaps: Vec<Gap>,
+    pub top_tags: Vec<ValueCount>,
+    pub top_names: Vec<ValueCount>,
+}
+
+/// Horizontal bars scaled to the largest value.
+#[component]
+fn BarChart(title: &'static str, rows: Vec<(String, u64)>) -> impl IntoView {
+    let max = rows.iter().map(|(_, v)| *v).max().unwrap_or(0).max(1);
+    view! {
+        <div style="padding: var(--contentSpacing);color: var(--lightColor);">
+            <div style="font-weight: bold;">{title}</div>
+            {rows
+                .into_iter()
+                .map(|(label, value)| {
+                    let width = format!("{}%", value * 100 / max);
+                    view! {
+                        <div style="display: flex;flex-direction: row;align-items: center;gap: var(--contentSpacing);font-size: 0.8em;">
+                            <span style="width: 8em;flex: none;overflow: hidden;text-overflow: ellipsis;white-space: nowrap;">{label}</span>
+                            <div style="flex: 1;">
+                                <div style="background-color: var(--accentColor3);height: 1em;" style:width=width></div>
+                            </div>
+                            <span style="width: 3em;flex: none;text-align: right;">{value}</span>
+                        </div>
+                    }
+                })
+                .collect_view()}
+        </div>
+    }
+}
+
+fn format_duration(minutes: i64) -> String {
+    format!("{}h {:02}m", minutes / 60, minutes % 60)
+}
+
+/// Entry point of the statistics of the last year. Rendered in every create slot, but shown only once like the health banner.
+#[component]
+pub fn StatsEntry(entries: FirstMounted) -> impl IntoView {
+    let first = entries.register();
+    let end = Utc::now();
+    let range = TimeRange {
+        start: end - TimeDelta::try_days(365).unwrap(),
+        end,
+    };
+    view! {
+        <Show when=move || first.get()>
+            <StatsDashboard range=range.clone() />
+        </Show>
+    }
+}
+
+/// Charts of the experiences in `range`, loaded when the dashboard is opened.
+#[component]
+pub fn StatsDashboard(range: TimeRange) -> impl IntoView {
+    let open = create_rw_signal(false);
+    let stats = create_local_resource(
+        move || open.get(),
+        move |open| {
+            let path = format!(
+                "/stats?start={}&end={}",
+                encode_uri_component(&range.start.to_rfc3339()),
+                encode_uri_component(&range.end.to_rfc3339())
+            );
+            async move {
+                if !open {
+                    return None;
+                }
+                Some(plugin_get::<Stats>(&path).await)
+            }
+        },
+    );
+
+    view! {
+        <Band color="var(--accentColor2)" click=Callback::new(move |_| open.update(|v| *v = !*v))>
+            {t(Message::Statistics)}
+        </Band>
+        <Show when=move || open.get()>
+            <StyledView>
+                {move || match stats.get().flatten() {
+                    None => view! {
+                        <div style="padding: var(--contentSpacing);color: var(--lightColor);">{t(Message::Loading)}</div>
+                    }.into_view(),
+                    Some(Err(e)) => view! {
+                        <div style="padding: var(--contentSpacing);color: var(--lightColor);">{e}</div>
+                    }.into_view(),
+                    Some(Ok(stats)) => {
+                        let period_rows = |v: Vec<PeriodCount>| v.into_iter().map(|v| (v.period, v.count)).collect::<Vec<_>>();
+                        let value_rows = |v: Vec<ValueCount>| v.into_iter().map(|v| (v.value, v.count)).collect::<Vec<_>>();
+                        view! {
+                            <div style="padding: var(--contentSpacing);color: var(--lightColor);">
+                                {t_format(Message::RangeDuration, &stats.range_experiences.to_string())}
+                                " "
+                                {format_duration(stats.range_minutes)}
+                            </div>
+                            <BarChart title=t(Message::PerMonth) rows=period_rows(stats.months) />
+                            <BarChart title=t(Message::PerWeek) rows=period_rows(stats.weeks) />
+                            <BarChart title=t(Message::TopTags) rows=value_rows(stats.top_tags) />
+                            <BarChart title=t(Message::TopNames) rows=value_rows(stats.top_names) />
+                            <div style="padding: var(--contentSpacing);color: var(--lightColor);">
+                                <div style="font-weight: bold;">{t(Message::LongestGaps)}</div>
+                                {stats
+                                    .longest_gaps
+                                    .into_iter()
+                                    .map(|gap| view! {
+                                        <div style="font-size: 0.8em;">
+                                            {format!(
+                                                "{} · {}",
+                                                format_timing(&Timing::Range(TimeRange { start: gap.from, end: gap.to })),
+                                                format_duration(gap.minutes)
+                                            )}
+                                        </div>
+                                    })
+                                    .collect_view()}
+                            </div>
+                        }.into_view()
+                    }
+                }}
+            </StyledView>
+        </Show>
+    }
+}
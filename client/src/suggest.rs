@@ -0,0 +1,143 @@
+use {
+    crate::{
+        i18n::{format_timing, t, Message},
+        plugin_get,
+    },
+    client_api::types::timing::Timing,
+    leptos::{
+        component, create_rw_signal, event_target_value, html::Input, set_timeout, spawn_local,
+        view, For, IntoView, NodeRef, Show, Signal, SignalGet, SignalGetUntracked, SignalSet,
+        SignalUpdate,
+    },
+    serde::Deserialize,
+    std::time::Duration,
+};
+
+/// Delay after the last keystroke before suggestions are requested.
+const DEBOUNCE_MS: u64 = 250;
+/// Matches the server, which suggests nothing for shorter prefixes.
+const MIN_PREFIX_CHARS: usize = 2;
+
+#[derive(Deserialize, Clone)]
+pub struct NameSuggestion {
+    pub name: String,
+    pub count: u32,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct SimilarExperience {
+    pub id: String,
+    pub name: String,
+    pub time: Timing,
+}
+
+#[derive(Deserialize, Clone, Default)]
+pub struct Suggestions {
+    pub names: Vec<NameSuggestion>,
+    pub similar: Vec<SimilarExperience>,
+}
+
+/// Name input of the create form with a dropdown of previously used names and a warning about likely duplicates near `around`.
+#[component]
+pub fn NameInput(
+    name_ref: NodeRef<Input>,
+    /// Timing picked in the form. Read on every keystroke, so similar experiences are looked up near the current pick.
+    around: Signal<Timing>,
+) -> impl IntoView {
+    let suggestions = create_rw_signal(Suggestions::default());
+    let open = create_rw_signal(false);
+    // Only the answer to the latest keystroke is shown.
+    let generation = create_rw_signal(0u32);
+    let on_input = move |e| {
+        let prefix = event_target_value(&e);
+        generation.update(|v| *v += 1);
+        if prefix.trim().chars().count() < MIN_PREFIX_CHARS {
+            open.set(false);
+            return;
+        }
+        let current = generation.get_untracked();
+        let around = match around.get_untracked() {
+            Timing::Instant(v) => v.to_rfc3339(),
+            Timing::Range(v) => v.start.to_rfc3339(),
+        };
+        set_timeout(
+            move || {
+                if generation.get_untracked() != current {
+                    return;
+                }
+                spawn_local(async move {
+                    let path = format!(
+                        "/suggest?prefix={}&around={}",
+                        js_sys::encode_uri_component(&prefix),
+                        js_sys::encode_uri_component(&around)
+                    );
+                    if let Ok(v) = plugin_get::<Suggestions>(&path).await {
+                        if generation.get_untracked() == current {
+                            suggestions.set(v);
+                            open.set(true);
+                        }
+                    }
+                });
+            },
+            Duration::from_millis(DEBOUNCE_MS),
+        );
+    };
+
+    let pick = move |name: String| {
+        if let Some(input) = name_ref.get_untracked() {
+            input.set_value(&name);
+        }
+        open.set(false);
+    };
+
+    view! {
+        <label style="display: block; position: relative;">
+            <span class="visually_hidden">{t(Message::Name)}</span>
+            <input
+                ref=name_ref
+                class="name_input"
+                placeholder=t(Message::Name)
+                autocomplete="off"
+                aria-autocomplete="list"
+                on:input=on_input
+                on:blur=move |_| set_timeout(move || open.set(false), Duration::from_millis(150))
+            />
+        </label>
+        <Show when=move || open.get() && !suggestions.get().names.is_empty()>
+            <ul role="listbox" style="list-style: none;margin: 0;padding: 0;background-color: var(--accentColor1);color: var(--lightColor);">
+                <For
+                    each=move || suggestions.get().names
+                    key=|v| v.name.clone()
+                    let:suggestion
+                >
+                    {
+                        let name = suggestion.name.clone();
+                        view! {
+                            <li
+                                role="option"
+                                tabindex="0"
+                                style="padding: calc(var(--contentSpacing) / 2) var(--contentSpacing);cursor: pointer;"
+                                on:mousedown=move |_| pick(name.clone())
+                            >
+                                {suggestion.name}
+                                <span style="opacity: 0.6;">{format!(" ×{}", suggestion.count)}</span>
+                            </li>
+                        }
+                    }
+                </For>
+            </ul>
+        </Show>
+        <Show when=move || !suggestions.get().similar.is_empty()>
+            <div role="alert" style="padding: calc(var(--contentSpacing) / 2) var(--contentSpacing);background-color: var(--accentColor2);color: var(--lightColor);font-size: 0.8em;">
+                {t(Message::PossibleDuplicate)}
+                <For
+                    each=move || suggestions.get().similar
+                    key=|v| v.id.clone()
+                    let:experience
+                >
+                    <div>{format!("{} · {}", experience.name, format_timing(&experience.time))}</div>
+                </For>
+            </div>
+        </Show>
+    }
+}
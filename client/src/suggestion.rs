@@ -0,0 +1,91 @@
+use {
+    crate::{
+        api_request,
+        i18n::{format_timing, t, t_format, Message},
+        Band,
+    },
+    client_api::types::timing::Timing,
+    leptos::{
+        component, create_node_ref, create_rw_signal, html::Input, spawn_local, view, window,
+        Callback, IntoView, Show, SignalGet, SignalSet,
+    },
+    serde::Serialize,
+};
+
+#[derive(Serialize)]
+struct AcceptSuggestionRequest {
+    name: Option<String>,
+    idempotency_key: Option<String>,
+}
+
+/// Experience proposed by the server's analyzer. Accepting creates it with the (editable) name, dismissing hides it for good.
+#[component]
+pub fn SuggestionCard(id: String, name: String, time: Timing, event_count: usize) -> impl IntoView {
+    let handled = create_rw_signal(false);
+    let name_ref = create_node_ref::<Input>();
+    let details = format!(
+        "{} · {}",
+        format_timing(&time),
+        t_format(Message::SuggestionEvents, &event_count.to_string())
+    );
+
+    let accept = {
+        let id = id.clone();
+        move |_| {
+            let request = AcceptSuggestionRequest {
+                name: name_ref.get().map(|v| v.value()),
+                idempotency_key: window().crypto().ok().map(|v| v.random_uuid()),
+            };
+            let path = format!("/plugin/timeline_plugin_experience/suggestions/{}/accept", id);
+            handled.set(true);
+            spawn_local(async move {
+                // The created experience arrives through the live updates.
+                let result: Result<String, _> = api_request(&path, &request).await;
+                if let Err(e) = result {
+                    handled.set(false);
+                    let _ = window()
+                        .alert_with_message(&format!("{}: {}", t(Message::UnableToCreate), e));
+                }
+            });
+        }
+    };
+    let dismiss = move |_| {
+        let path = format!("/plugin/timeline_plugin_experience/suggestions/{}/dismiss", id);
+        handled.set(true);
+        spawn_local(async move {
+            let result: Result<(), _> = api_request(&path, &()).await;
+            if let Err(e) = result {
+                handled.set(false);
+                let _ = window()
+                    .alert_with_message(&format!("{}: {}", t(Message::UnableToDismiss), e));
+            }
+        });
+    };
+
+    view! {
+        <Show when=move || !handled.get()>
+            <div
+                style="display: flex; flex-direction: column;border: 2px dashed var(--accentColor1);"
+                role="group"
+                aria-label=t(Message::SuggestedExperience)
+            >
+                <div style="padding: var(--contentSpacing);color: var(--lightColor);font-size: 0.8em;">
+                    {t(Message::SuggestedExperience)}
+                </div>
+                <label style="display: block;">
+                    <span class="visually_hidden">{t(Message::Name)}</span>
+                    <input ref=name_ref class="name_input" value=name.clone() />
+                </label>
+                <div style="padding: var(--contentSpacing);color: var(--lightColor);font-size: 0.8em;">
+                    {details.clone()}
+                </div>
+                <div style="display: flex; flex-direction: row;">
+                    <Band click=Callback::new(accept.clone())>{t(Message::Accept)}</Band>
+                    <Band color="var(--darkColor)" click=Callback::new(dismiss.clone())>
+                        {t(Message::Dismiss)}
+                    </Band>
+                </div>
+            </div>
+        </Show>
+    }
+}
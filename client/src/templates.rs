@@ -0,0 +1,88 @@
+use {
+    crate::{api_request, i18n::{t, Message}, Band},
+    client_api::types::timing::Timing,
+    leptos::{
+        component, html::Input, spawn_local, view, window, Callback, For, IntoView, NodeRef,
+        RwSignal, Signal, SignalGet, SignalGetUntracked,
+    },
+    serde::{Deserialize, Serialize},
+};
+
+#[derive(Deserialize, Clone)]
+pub struct ExperienceTemplate {
+    pub name: String,
+    pub pattern: String,
+    #[serde(default)]
+    pub color: Option<String>,
+    #[serde(default)]
+    pub icon: Option<String>,
+}
+
+#[derive(Serialize)]
+struct CreateFromTemplateRequest {
+    template: String,
+    time: Timing,
+    text: String,
+    idempotency_key: Option<String>,
+}
+
+/// One button per template. The text of the name input fills the template's custom placeholders.
+#[component]
+pub fn TemplateButtons(
+    templates: RwSignal<Vec<ExperienceTemplate>>,
+    name_ref: NodeRef<Input>,
+    #[prop(into)] time: Signal<Timing>,
+) -> impl IntoView {
+    view! {
+        <div style="display: flex; flex-direction: row; flex-wrap: wrap;">
+            <For each=move || templates.get() key=|v| v.name.clone() let:template>
+                {
+                    let name = template.name.clone();
+                    let label = match &template.icon {
+                        Some(icon) => format!("{} {}", icon, template.name),
+                        None => template.name.clone(),
+                    };
+                    let color = template
+                        .color
+                        .clone()
+                        .unwrap_or_else(|| "var(--accentColor3)".to_string());
+                    view! {
+                        <div style="flex: 1 1 auto;">
+                            <Band
+                                color=color
+                                label=format!("{}: {}", t(Message::Template), template.pattern)
+                                click=Callback::new(move |_| {
+                                    let input = name_ref.get().unwrap();
+                                    let request = CreateFromTemplateRequest {
+                                        template: name.clone(),
+                                        time: time.get_untracked(),
+                                        text: input.value(),
+                                        idempotency_key: window().crypto().ok().map(|v| v.random_uuid()),
+                                    };
+                                    input.set_value("");
+                                    spawn_local(async move {
+                                        // The new experience arrives through the live updates.
+                                        let result: Result<String, _> = api_request(
+                                            "/plugin/timeline_plugin_experience/create_from_template",
+                                            &request,
+                                        )
+                                        .await;
+                                        if let Err(e) = result {
+                                            let _ = window().alert_with_message(&format!(
+                                                "{}: {}",
+                                                t(Message::UnableToCreate),
+                                                e
+                                            ));
+                                        }
+                                    });
+                                })
+                            >
+                                {label}
+                            </Band>
+                        </div>
+                    }
+                }
+            </For>
+        </div>
+    }
+}
@@ -0,0 +1,106 @@
+use {
+    chrono_tz::Tz,
+    client_api::types::{
+        external::chrono::{DateTime, LocalResult, TimeDelta, TimeZone, Timelike, Utc},
+        timing::Timing,
+    },
+    js_sys::{Array, Intl::DateTimeFormat, Object, Reflect},
+    leptos::{leptos_dom::is_browser, window},
+    serde::Deserialize,
+    std::cell::Cell,
+};
+
+/// Remembers that the user prefers the browser's time zone over the configured one.
+const BROWSER_TIMEZONE_KEY: &str = "timeline_plugin_experience_browser_timezone";
+
+#[derive(Deserialize)]
+pub struct ClientConfig {
+    pub timezone: String,
+}
+
+thread_local! {
+    static CONFIGURED: Cell<Option<Tz>> = const { Cell::new(None) };
+}
+
+pub fn set_configured(timezone: &str) {
+    CONFIGURED.with(|v| v.set(timezone.parse().ok()));
+}
+
+/// Time zone the browser runs in, if chrono knows it.
+pub fn browser() -> Option<Tz> {
+    if !is_browser() {
+        return None;
+    }
+    let options = DateTimeFormat::new(&Array::new(), &Object::new()).resolved_options();
+    Reflect::get(&options, &"timeZone".into())
+        .ok()?
+        .as_string()?
+        .parse()
+        .ok()
+}
+
+pub fn uses_browser() -> bool {
+    is_browser()
+        && window()
+            .local_storage()
+            .ok()
+            .flatten()
+            .and_then(|v| v.get_item(BROWSER_TIMEZONE_KEY).ok().flatten())
+            .is_some()
+}
+
+pub fn set_uses_browser(value: bool) {
+    if let Some(storage) = window().local_storage().ok().flatten() {
+        let _ = if value {
+            storage.set_item(BROWSER_TIMEZONE_KEY, "true")
+        } else {
+            storage.remove_item(BROWSER_TIMEZONE_KEY)
+        };
+    }
+}
+
+/// Time zone times are shown and entered in: the configured one unless the user chose the browser's.
+pub fn current() -> Tz {
+    let configured = CONFIGURED.with(|v| v.get());
+    match (uses_browser(), configured) {
+        (false, Some(v)) => v,
+        _ => browser().unwrap_or(Tz::UTC),
+    }
+}
+
+/// Create slots for the hour starting at `slot`, which the server generates at the full hours of the configured time zone.
+/// With the browser's time zone chosen, the slots start at its full hours instead. They only differ for zones whose offset
+/// has other minutes, or around daylight saving changes the zones do not share.
+pub fn slots_in(slot: &Timing) -> Vec<Timing> {
+    let start = match slot {
+        Timing::Instant(v) => *v,
+        Timing::Range(v) => v.start,
+    };
+    if !uses_browser() {
+        return vec![slot.clone()];
+    }
+    let timezone = current();
+    let end = start + TimeDelta::try_hours(1).unwrap();
+    let local_start = start.with_timezone(&timezone).naive_local();
+    let mut hour = local_start
+        .date()
+        .and_hms_opt(local_start.hour(), 0, 0)
+        .unwrap();
+    let mut result = Vec::new();
+    loop {
+        let candidates: Vec<DateTime<Utc>> = match timezone.from_local_datetime(&hour) {
+            LocalResult::Single(v) => vec![v.with_timezone(&Utc)],
+            LocalResult::Ambiguous(a, b) => vec![a.with_timezone(&Utc), b.with_timezone(&Utc)],
+            LocalResult::None => Vec::new(),
+        };
+        for candidate in candidates {
+            if candidate >= end {
+                return result;
+            }
+            if candidate >= start {
+                result.push(Timing::Instant(candidate));
+            }
+        }
+        hour += TimeDelta::try_hours(1).unwrap();
+    }
+}
@@ -0,0 +1,201 @@
+use {
+    crate::{
+        i18n::{t, Message},
+        PLUGIN_API, REQUESTED_WITH,
+    },
+    client_api::types::{api::APIResult, timing::Timing},
+    js_sys::{encode_uri_component, Function, Promise},
+    leptos::{
+        component, create_node_ref, create_rw_signal, ev::DragEvent, html::Input, spawn_local,
+        view, window, For, IntoView, NodeRef, RwSignal, Signal, SignalGet, SignalGetUntracked,
+        SignalUpdate,
+    },
+    serde::de::DeserializeOwned,
+    wasm_bindgen::{closure::Closure, JsCast, JsValue},
+    wasm_bindgen_futures::JsFuture,
+    web_sys::{File, FileList, ProgressEvent, XmlHttpRequest},
+};
+
+fn js_error(e: JsValue) -> String {
+    e.as_string().unwrap_or_else(|| format!("{:?}", e))
+}
+
+/// Posts `file` as the raw request body to one of this plugin's routes. `progress` receives the uploaded fraction.
+/// `fetch` can not report upload progress, so this uses `XMLHttpRequest`.
+pub async fn post_file<T: DeserializeOwned>(
+    path: &str,
+    file: &File,
+    progress: impl Fn(f64) + 'static,
+) -> Result<T, String> {
+    let xhr = XmlHttpRequest::new().map_err(js_error)?;
+    xhr.open("POST", &format!("{}{}", PLUGIN_API, path))
+        .map_err(js_error)?;
+    xhr.set_request_header(REQUESTED_WITH.0, REQUESTED_WITH.1)
+        .map_err(js_error)?;
+    let on_progress = Closure::<dyn Fn(ProgressEvent)>::new(move |e: ProgressEvent| {
+        if e.length_computable() && e.total() > 0.0 {
+            progress(e.loaded() / e.total());
+        }
+    });
+    xhr.upload()
+        .map_err(js_error)?
+        .set_onprogress(Some(on_progress.as_ref().unchecked_ref()));
+    let done = Promise::new(&mut |resolve: Function, reject: Function| {
+        xhr.set_onload(Some(&resolve));
+        xhr.set_onerror(Some(&reject));
+        xhr.set_onabort(Some(&reject));
+    });
+    xhr.send_with_opt_blob(Some(file)).map_err(js_error)?;
+    let result = JsFuture::from(done).await;
+    drop(on_progress);
+    result.map_err(|_| t(Message::UploadFailed).to_string())?;
+
+    let text = xhr.response_text().map_err(js_error)?.unwrap_or_default();
+    serde_json::from_str::<APIResult<T>>(&text)
+        .map_err(|e| e.to_string())?
+        .map_err(|e| e.to_string())
+}
+
+pub(crate) fn files(list: Option<FileList>) -> Vec<File> {
+    let Some(list) = list else {
+        return Vec::new();
+    };
+    (0..list.length()).filter_map(|i| list.get(i)).collect()
+}
+
+#[derive(Clone)]
+pub struct UploadProgress {
+    pub key: u32,
+    pub filename: String,
+    pub progress: RwSignal<f64>,
+}
+
+/// Upload entries shown while files are sent, each removed when its upload finished.
+#[derive(Clone, Copy)]
+pub struct Uploads {
+    items: RwSignal<Vec<UploadProgress>>,
+    next_key: RwSignal<u32>,
+}
+
+impl Uploads {
+    pub fn new() -> Self {
+        Uploads {
+            items: create_rw_signal(Vec::new()),
+            next_key: create_rw_signal(0),
+        }
+    }
+
+    /// Sends `file` to `path` while showing its progress. `done` receives the result.
+    pub fn send<T: DeserializeOwned + 'static>(
+        self,
+        path: String,
+        file: File,
+        done: impl FnOnce(Result<T, String>) + 'static,
+    ) {
+        let key = self.next_key.get_untracked();
+        self.next_key.update(|v| *v += 1);
+        let progress = create_rw_signal(0.0);
+        self.items.update(|v| {
+            v.push(UploadProgress {
+                key,
+                filename: file.name(),
+                progress,
+            })
+        });
+        spawn_local(async move {
+            let result = post_file::<T>(&path, &file, move |v| progress.set(v)).await;
+            self.items.update(|v| v.retain(|item| item.key != key));
+            done(result);
+        });
+    }
+
+    pub fn view(self) -> impl IntoView {
+        view! {
+            <For each=move || self.items.get() key=|item| item.key let:item>
+                <div style="padding: var(--contentSpacing);color: var(--lightColor);display: flex;flex-direction: row;gap: var(--contentSpacing);">
+                    <span style="flex: 1;overflow: hidden;text-overflow: ellipsis;">{item.filename.clone()}</span>
+                    <progress max="1" value=move || item.progress.get() aria-label=item.filename.clone()></progress>
+                </div>
+            </For>
+        }
+    }
+}
+
+impl Default for Uploads {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Drop zone and file picker of the create form. Every photo or video becomes an experience named after the name input, or after the file when it is empty.
+#[component]
+pub fn UploadDrop(name_ref: NodeRef<Input>, #[prop(into)] fallback: Signal<Timing>) -> impl IntoView {
+    let uploads = Uploads::new();
+    let dragging = create_rw_signal(false);
+    let picker = create_node_ref::<Input>();
+
+    let upload = move |files: Vec<File>| {
+        let name = name_ref.get().map(|v| v.value()).unwrap_or_default();
+        let fallback = match fallback.get_untracked() {
+            Timing::Instant(v) => v,
+            Timing::Range(v) => v.start,
+        };
+        for file in files {
+            let key = window()
+                .crypto()
+                .ok()
+                .map(|v| v.random_uuid())
+                .unwrap_or_default();
+            let path = format!(
+                "/upload?filename={}&name={}&fallback={}&idempotency_key={}",
+                encode_uri_component(&file.name()),
+                encode_uri_component(&name),
+                encode_uri_component(&fallback.to_rfc3339()),
+                key
+            );
+            // The new experience arrives through the live updates.
+            uploads.send::<String>(path, file, |result| {
+                if let Err(e) = result {
+                    let _ = window()
+                        .alert_with_message(&format!("{}: {}", t(Message::UnableToCreate), e));
+                }
+            });
+        }
+        if let Some(input) = name_ref.get() {
+            input.set_value("");
+        }
+    };
+
+    view! {
+        <label
+            style="display: block;padding: var(--contentSpacing);color: var(--lightColor);text-align: center;border: 2px dashed var(--lightColor);cursor: pointer;"
+            style:opacity=move || if dragging.get() { "1" } else { "0.7" }
+            on:dragover=move |e: DragEvent| {
+                e.prevent_default();
+                dragging.set(true);
+            }
+            on:dragleave=move |_| dragging.set(false)
+            on:drop=move |e: DragEvent| {
+                e.prevent_default();
+                dragging.set(false);
+                upload(files(e.data_transfer().and_then(|v| v.files())));
+            }
+        >
+            {t(Message::DropMedia)}
+            <input
+                ref=picker
+                type="file"
+                class="visually_hidden"
+                accept="image/*,video/*"
+                multiple
+                on:change=move |_| {
+                    if let Some(input) = picker.get() {
+                        upload(files(input.files()));
+                        input.set_value("");
+                    }
+                }
+            />
+        </label>
+        {uploads.view()}
+    }
+}
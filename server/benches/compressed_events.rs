@@ -0,0 +1,81 @@
+//! Measures `find_compressed_events` for a year-long range over a collection of 100k experiences.
+//! Expects a MongoDB instance at `EXPERIENCE_BENCH_MONGODB` (default `mongodb://localhost:27017`); the collection is reseeded on every run.
+
+use {
+    chrono_tz::Tz,
+    criterion::{criterion_group, criterion_main, Criterion},
+    serde::Serialize,
+    server_api::{
+        db::Event,
+        external::{
+            mongodb::{bson::Document, Client},
+            types::{
+                available_plugins::AvailablePlugins,
+                external::chrono::{TimeDelta, TimeZone, Utc},
+                timing::{TimeRange, Timing},
+            },
+        },
+    },
+    timeline_plugin_experience_server::{ensure_indexes, find_compressed_events, Locale},
+};
+
+const EVENT_COUNT: i64 = 100_000;
+
+#[derive(Serialize)]
+struct BenchExperience {
+    name: String,
+    id: String,
+}
+
+fn compressed_events(c: &mut Criterion) {
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    let url = std::env::var("EXPERIENCE_BENCH_MONGODB")
+        .unwrap_or_else(|_| "mongodb://localhost:27017".to_string());
+    let start = Utc.with_ymd_and_hms(2020, 1, 1, 0, 0, 0).unwrap();
+
+    let events = runtime.block_on(async {
+        let client = Client::with_uri_str(&url).await.unwrap();
+        let events = client
+            .database("timeline_plugin_experience_bench")
+            .collection::<Document>("events");
+        events.drop(None).await.unwrap();
+        ensure_indexes(&events).await.unwrap();
+
+        let typed = events.clone_with_type::<Event<BenchExperience>>();
+        let mut batch = Vec::new();
+        for i in 0..EVENT_COUNT {
+            // Spread the events over roughly five years.
+            let time = start + TimeDelta::try_minutes(i * 26).unwrap();
+            batch.push(Event {
+                timing: Timing::Instant(time),
+                id: i.to_string(),
+                plugin: AvailablePlugins::timeline_plugin_experience,
+                event: BenchExperience {
+                    name: format!("Experience {}", i),
+                    id: i.to_string(),
+                },
+            });
+            if batch.len() == 10_000 {
+                typed.insert_many(std::mem::take(&mut batch), None).await.unwrap();
+            }
+        }
+        events
+    });
+
+    let range = TimeRange {
+        start: Utc.with_ymd_and_hms(2022, 1, 1, 0, 0, 0).unwrap(),
+        end: Utc.with_ymd_and_hms(2023, 1, 1, 0, 0, 0).unwrap(),
+    };
+
+    c.bench_function("find_compressed_events year", |b| {
+        b.to_async(&runtime)
+            .iter(|| async { find_compressed_events(&events, &range, Locale::En, Tz::UTC).await.unwrap() })
+    });
+}
+
+criterion_group! {
+    name = benches;
+    config = Criterion::default().sample_size(10);
+    targets = compressed_events
+}
+criterion_main!(benches);
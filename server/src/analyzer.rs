@@ -0,0 +1,494 @@
+use {
+    crate::{
+        audit::{self, Actor, AuditEntry},
+        create_once,
+        csrf::SameOrigin,
+        experiences::RequestId,
+        i18n::{Locale, Message},
+        rate_limit::RateLimited,
+        covers, plugin_collection, respond, timing_end, timing_start, CompressedExperienceEvent,
+        ConfigData, CreateExperienceRequest, Created, Services,
+    },
+    chrono_tz::Tz,
+    serde::{Deserialize, Serialize},
+    server_api::{
+        db::Database,
+        external::{
+            futures::TryStreamExt,
+            mongodb::{
+                bson::{self, doc, Document},
+                options::{FindOptions, UpdateOptions},
+            },
+            rocket::{
+                fairing::AdHoc,
+                http::{CookieJar, Status},
+                post,
+                response::status::Custom,
+                serde::json::Json,
+                tokio,
+            },
+            types::{
+                api::{APIError, APIResult, CompressedEvent},
+                available_plugins::AvailablePlugins,
+                external::{
+                    chrono::{DateTime, TimeDelta, Utc},
+                    serde_json,
+                },
+                timing::{TimeRange, Timing},
+            },
+        },
+    },
+    std::{cmp::Reverse, sync::Arc, time::Duration},
+};
+
+/// Settings of the background analyzer. It only runs when this section exists in the config.
+#[derive(Deserialize, Clone)]
+pub struct SuggestionConfig {
+    #[serde(default = "default_interval_minutes")]
+    pub interval_minutes: u64,
+    /// How far back the timeline is scanned.
+    #[serde(default = "default_lookback_days")]
+    pub lookback_days: i64,
+    /// Events further apart than this end a burst.
+    #[serde(default = "default_max_gap_minutes")]
+    pub max_gap_minutes: i64,
+    /// Events a burst needs to be suggested.
+    #[serde(default = "default_min_events")]
+    pub min_events: usize,
+    /// Bursts with enough location points outside of it are suggested as trips.
+    #[serde(default)]
+    pub home: Option<Home>,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct Home {
+    pub latitude: f64,
+    pub longitude: f64,
+    pub radius_km: f64,
+    /// Location points outside of the radius a burst needs to be suggested as a trip.
+    #[serde(default = "default_min_away_events")]
+    pub min_events: usize,
+}
+
+fn default_interval_minutes() -> u64 {
+    60
+}
+
+fn default_lookback_days() -> i64 {
+    30
+}
+
+fn default_max_gap_minutes() -> i64 {
+    90
+}
+
+fn default_min_events() -> usize {
+    20
+}
+
+fn default_min_away_events() -> usize {
+    5
+}
+
+/// A burst of activity proposed as an experience. Stored in the `suggestions` collection; dismissed ones are kept, so they are not suggested again.
+#[derive(Serialize, Deserialize, Clone)]
+struct StoredSuggestion {
+    #[serde(rename = "_id")]
+    id: String,
+    name: String,
+    /// Milliseconds since the epoch.
+    start: i64,
+    end: i64,
+    event_count: usize,
+    dismissed: bool,
+}
+
+impl StoredSuggestion {
+    fn timing(&self) -> Timing {
+        Timing::Range(TimeRange {
+            start: DateTime::from_timestamp_millis(self.start).unwrap_or_default(),
+            end: DateTime::from_timestamp_millis(self.end).unwrap_or_default(),
+        })
+    }
+}
+
+#[derive(Deserialize)]
+struct TimingProjection {
+    timing: Timing,
+}
+
+#[derive(Deserialize, Default)]
+struct Location {
+    latitude: Option<f64>,
+    longitude: Option<f64>,
+}
+
+#[derive(Deserialize)]
+struct ActivityProjection {
+    timing: Timing,
+    #[serde(default)]
+    event: Option<Location>,
+}
+
+struct Burst {
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+    events: usize,
+    away: usize,
+}
+
+/// Great-circle distance in kilometers.
+fn distance_km(a: (f64, f64), b: (f64, f64)) -> f64 {
+    let (lat1, lon1) = (a.0.to_radians(), a.1.to_radians());
+    let (lat2, lon2) = (b.0.to_radians(), b.1.to_radians());
+    let h = ((lat2 - lat1) / 2.0).sin().powi(2)
+        + lat1.cos() * lat2.cos() * ((lon2 - lon1) / 2.0).sin().powi(2);
+    2.0 * 6371.0 * h.sqrt().asin()
+}
+
+/// Groups events, sorted by start, into bursts separated by gaps longer than `max_gap`.
+fn find_bursts(activity: &[ActivityProjection], max_gap: TimeDelta, home: Option<&Home>) -> Vec<Burst> {
+    let mut bursts: Vec<Burst> = Vec::new();
+    for event in activity {
+        let start = timing_start(&event.timing);
+        let end = timing_end(&event.timing);
+        let away = match (home, &event.event) {
+            (
+                Some(home),
+                Some(Location {
+                    latitude: Some(latitude),
+                    longitude: Some(longitude),
+                }),
+            ) => distance_km((home.latitude, home.longitude), (*latitude, *longitude)) > home.radius_km,
+            _ => false,
+        };
+        match bursts.last_mut() {
+            Some(burst) if start - burst.end <= max_gap => {
+                burst.end = burst.end.max(end);
+                burst.events += 1;
+                burst.away += away as usize;
+            }
+            _ => bursts.push(Burst {
+                start,
+                end,
+                events: 1,
+                away: away as usize,
+            }),
+        }
+    }
+    bursts
+}
+
+/// Most events of other plugins loaded per run. A busier lookback window is only analyzed up to it.
+const MAX_SCANNED_EVENTS: i64 = 100_000;
+
+/// Newest `MAX_SCANNED_EVENTS` events matching `filter`, oldest first. Instants and ranges are queried separately, each
+/// sorted by its own start field, so a capped scan still covers one contiguous stretch up to the end of the window.
+async fn find_newest_activity(database: &Database, filter: Document) -> APIResult<Vec<ActivityProjection>> {
+    let mut activity = Vec::new();
+    for field in ["timing.Instant", "timing.Range.start"] {
+        let found: Vec<ActivityProjection> = database
+            .get_events::<Document>()
+            .clone_with_type::<ActivityProjection>()
+            .find(
+                Database::combine_documents(filter.clone(), doc! {field: {"$exists": true}}),
+                FindOptions::builder()
+                    .projection(doc! {"_id": 0, "timing": 1, "event.latitude": 1, "event.longitude": 1})
+                    .sort(doc! {field: -1})
+                    .limit(MAX_SCANNED_EVENTS)
+                    .build(),
+            )
+            .await?
+            .try_collect()
+            .await?;
+        activity.extend(found);
+    }
+    activity.sort_by_key(|v| Reverse(timing_start(&v.timing)));
+    if activity.len() as i64 >= MAX_SCANNED_EVENTS {
+        tracing::warn!(
+            limit = MAX_SCANNED_EVENTS,
+            "too many events in the lookback window, only its newest part is analyzed"
+        );
+        activity.truncate(MAX_SCANNED_EVENTS as usize);
+    }
+    activity.reverse();
+    Ok(activity)
+}
+
+/// Id of the suggestion for a burst. Only the start is used, so a burst that grows keeps its suggestion.
+fn suggestion_id(burst: &Burst) -> String {
+    format!("burst-{}", burst.start.timestamp_millis())
+}
+
+/// Scans the other plugins' events of the lookback window and stores a suggestion per burst found. Open suggestions of
+/// bursts that are no longer found are removed, the others are updated in place and keep their id.
+async fn analyze(
+    database: &Database,
+    config: &SuggestionConfig,
+    locale: Locale,
+    timezone: Tz,
+) -> APIResult<()> {
+    let now = Utc::now();
+    let window = TimeRange {
+        start: now - TimeDelta::try_days(config.lookback_days).unwrap_or_default(),
+        end: now,
+    };
+    let own_plugin = bson::to_bson(&AvailablePlugins::timeline_plugin_experience)
+        .map_err(|e| APIError::Custom(e.to_string()))?;
+    let activity = find_newest_activity(
+        database,
+        Database::combine_documents(
+            Database::generate_range_filter(&window),
+            doc! {"plugin": {"$ne": own_plugin}},
+        ),
+    )
+    .await?;
+
+    let suggestions = plugin_collection::<StoredSuggestion>(database, "suggestions");
+    let dismissed: Vec<StoredSuggestion> = suggestions
+        .find(doc! {"dismissed": true}, None)
+        .await?
+        .try_collect()
+        .await?;
+    // Loaded once for the whole window instead of once per burst.
+    let experiences: Vec<TimingProjection> = database
+        .get_events::<Document>()
+        .clone_with_type::<TimingProjection>()
+        .find(
+            Database::combine_documents(
+                Database::generate_range_filter(&window),
+                Database::generate_find_plugin_filter(AvailablePlugins::timeline_plugin_experience),
+            ),
+            FindOptions::builder()
+                .projection(doc! {"_id": 0, "timing": 1})
+                .build(),
+        )
+        .await?
+        .try_collect()
+        .await?;
+
+    let mut found = Vec::new();
+    for burst in find_bursts(
+        &activity,
+        TimeDelta::try_minutes(config.max_gap_minutes).unwrap_or_default(),
+        config.home.as_ref(),
+    ) {
+        let trip = config.home.as_ref().is_some_and(|home| burst.away >= home.min_events);
+        if burst.events < config.min_events && !trip {
+            continue;
+        }
+        let id = suggestion_id(&burst);
+        if dismissed.iter().any(|v| {
+            v.id == id || (v.start < burst.end.timestamp_millis() && burst.start.timestamp_millis() < v.end)
+        }) {
+            continue;
+        }
+        let range = TimeRange {
+            start: burst.start,
+            end: burst.end,
+        };
+        let burst_timing = Timing::Range(range);
+        if experiences.iter().any(|v| covers(&v.timing, &burst_timing)) {
+            continue;
+        }
+        let date = burst.start.with_timezone(&timezone).format("%Y-%m-%d").to_string();
+        let message = if trip {
+            Message::SuggestedTrip
+        } else {
+            Message::SuggestedActivity
+        };
+        found.push(StoredSuggestion {
+            id,
+            name: locale.format(message, &date),
+            start: burst.start.timestamp_millis(),
+            end: burst.end.timestamp_millis(),
+            event_count: burst.events,
+            dismissed: false,
+        });
+    }
+
+    let ids = found.iter().map(|v| v.id.clone()).collect::<Vec<_>>();
+    for suggestion in found {
+        suggestions
+            .update_one(
+                doc! {"_id": &suggestion.id},
+                doc! {
+                    "$set": {
+                        "name": &suggestion.name,
+                        "start": suggestion.start,
+                        "end": suggestion.end,
+                        "event_count": suggestion.event_count as i64,
+                    },
+                    "$setOnInsert": {"dismissed": false},
+                },
+                UpdateOptions::builder().upsert(true).build(),
+            )
+            .await?;
+    }
+    suggestions
+        .delete_many(doc! {"dismissed": false, "_id": {"$nin": ids}}, None)
+        .await?;
+    Ok(())
+}
+
+/// Runs the analyzer every `interval_minutes` after launch, if it is configured.
+pub fn fairing(config: &ConfigData) -> AdHoc {
+    let settings = config.suggestions.clone();
+    let locale = config.locale;
+    let timezone = config.timezone;
+    AdHoc::on_liftoff("Experience suggestions", move |rocket| {
+        Box::pin(async move {
+            let (Some(settings), Some(database)) = (settings, rocket.state::<Arc<Database>>().cloned()) else {
+                return;
+            };
+            tokio::spawn(async move {
+                let mut interval =
+                    tokio::time::interval(Duration::from_secs(settings.interval_minutes.max(1) * 60));
+                loop {
+                    interval.tick().await;
+                    if let Err(e) = analyze(&database, &settings, locale, timezone).await {
+                        tracing::warn!(error = %e, "unable to analyze timeline for suggestions");
+                    }
+                }
+            });
+        })
+    })
+}
+
+/// Open suggestions overlapping `query_range`, as timeline events.
+pub async fn find_suggestions(
+    database: &Database,
+    query_range: &TimeRange,
+) -> APIResult<Vec<CompressedEvent>> {
+    let mut suggestions: Vec<StoredSuggestion> = plugin_collection::<StoredSuggestion>(database, "suggestions")
+        .find(
+            doc! {
+                "dismissed": false,
+                "start": {"$lt": query_range.end.timestamp_millis()},
+                "end": {"$gt": query_range.start.timestamp_millis()},
+            },
+            None,
+        )
+        .await?
+        .try_collect()
+        .await?;
+    suggestions.sort_by_key(|v| v.start);
+    Ok(suggestions
+        .into_iter()
+        .map(|v| CompressedEvent {
+            title: v.name.clone(),
+            time: v.timing(),
+            data: serde_json::to_value(CompressedExperienceEvent::Suggestion {
+                id: v.id,
+                name: v.name,
+                time: v.timing(),
+                event_count: v.event_count,
+            })
+            .unwrap(),
+        })
+        .collect())
+}
+
+async fn find_open(services: &Services<'_>, id: &str) -> Result<StoredSuggestion, (Status, APIError)> {
+    plugin_collection::<StoredSuggestion>(services.database, "suggestions")
+        .find_one(doc! {"_id": id, "dismissed": false}, None)
+        .await
+        .map_err(|e| (Status::InternalServerError, e.into()))?
+        .ok_or_else(|| {
+            (
+                Status::NotFound,
+                APIError::Custom(format!("Unknown suggestion: {}", id)),
+            )
+        })
+}
+
+#[derive(Deserialize)]
+pub struct AcceptSuggestionRequest {
+    /// Replaces the proposed name.
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub idempotency_key: Option<String>,
+}
+
+/// Creates the suggested experience. Returns its id.
+#[post("/suggestions/<id>/accept", data = "<request>")]
+#[allow(clippy::too_many_arguments)]
+pub async fn accept_suggestion(
+    _same_origin: SameOrigin,
+    _rate_limit: RateLimited,
+    id: &str,
+    request: Json<AcceptSuggestionRequest>,
+    cookies: &CookieJar<'_>,
+    services: Services<'_>,
+    request_id: RequestId,
+    actor: Actor,
+) -> Custom<Json<APIResult<String>>> {
+    let mut before = None;
+    let result = async {
+        services.authenticate(cookies)?;
+        let suggestion = find_open(&services, id).await?;
+        before = bson::to_document(&suggestion).ok();
+        let request = request.into_inner();
+        let create_request = CreateExperienceRequest {
+            name: request
+                .name
+                .filter(|v| !v.trim().is_empty())
+                .unwrap_or_else(|| suggestion.name.clone()),
+            time: suggestion.timing(),
+            tags: Vec::new(),
+            idempotency_key: request.idempotency_key,
+            color: None,
+            icon: None,
+            parent_id: None,
+            series_name: None,
+            sources: Vec::new(),
+        };
+        let experience = create_once(&services, &create_request, None, &request_id).await?;
+        plugin_collection::<StoredSuggestion>(services.database, "suggestions")
+            .delete_one(doc! {"_id": id}, None)
+            .await
+            .map_err(|e| (Status::InternalServerError, e.into()))?;
+        Ok(experience)
+    }
+    .await;
+    audit::record_created(&services, &actor, "accept_suggestion", &request_id, before, &result).await;
+    respond(result.map(Created::into_id))
+}
+
+/// Hides a suggestion for good. Later runs of the analyzer skip bursts overlapping it.
+#[post("/suggestions/<id>/dismiss")]
+pub async fn dismiss_suggestion(
+    _same_origin: SameOrigin,
+    _rate_limit: RateLimited,
+    id: &str,
+    cookies: &CookieJar<'_>,
+    services: Services<'_>,
+    request_id: RequestId,
+    actor: Actor,
+) -> Custom<Json<APIResult<()>>> {
+    let mut before = None;
+    let result = async {
+        services.authenticate(cookies)?;
+        let mut suggestion = find_open(&services, id).await?;
+        before = bson::to_document(&suggestion).ok();
+        plugin_collection::<StoredSuggestion>(services.database, "suggestions")
+            .update_one(doc! {"_id": id}, doc! {"$set": {"dismissed": true}}, None)
+            .await
+            .map_err(|e| (Status::InternalServerError, e.into()))?;
+        suggestion.dismissed = true;
+        Ok(bson::to_document(&suggestion).ok())
+    }
+    .await;
+    let (after, result) = match result {
+        Ok(after) => (after, Ok(())),
+        Err(e) => (None, Err(e)),
+    };
+    let entry = AuditEntry::new(&actor, "dismiss_suggestion", &request_id)
+        .target(Some(id))
+        .before(before)
+        .after(after)
+        .result(&result);
+    audit::record(services.database, entry).await;
+    respond(result)
+}
@@ -0,0 +1,345 @@
+use {
+    crate::{
+        experience_filter, experiences::RequestId, is_authenticated, plugin_collection, respond,
+        Created, DatabaseExperience, Services, AUTH_COOKIE,
+    },
+    serde::{Deserialize, Serialize},
+    server_api::{
+        db::Database,
+        external::{
+            futures::TryStreamExt,
+            mongodb::{
+                bson::{self, doc, Bson, Document},
+                options::FindOptions,
+                IndexModel,
+            },
+            rocket::{
+                get,
+                http::{CookieJar, Status},
+                request::{FromRequest, Outcome, Request},
+                response::status::Custom,
+                serde::json::Json,
+            },
+            types::{
+                api::{APIError, APIResult},
+                external::chrono::Utc,
+            },
+        },
+    },
+    hmac::{Hmac, Mac},
+    sha2::Sha256,
+    std::convert::Infallible,
+    uuid::Uuid,
+};
+
+const AUDIT_COLLECTION: &str = "audit";
+const MAX_PER_PAGE: u64 = 200;
+/// Highest page listed. Larger ones would skip past what the driver accepts, and no log gets this long.
+const MAX_PAGE: u64 = 1_000_000;
+
+/// Server-side key of the actor HMAC. An unkeyed hash of the shared password could be checked against guesses offline.
+pub struct AuditKey(Vec<u8>);
+
+impl AuditKey {
+    /// Uses `secret` from the config, or a random key if none is configured.
+    pub fn new(secret: Option<&str>) -> Self {
+        match secret {
+            Some(secret) => AuditKey(secret.as_bytes().to_vec()),
+            None => {
+                tracing::warn!("no audit_secret configured, audit actors change with every restart");
+                AuditKey(
+                    [Uuid::new_v4(), Uuid::new_v4()]
+                        .iter()
+                        .flat_map(|v| v.into_bytes())
+                        .collect(),
+                )
+            }
+        }
+    }
+}
+
+/// Whoever sent the request, identified by an HMAC of the auth cookie, so the log never contains the secret itself.
+/// Only a cookie that authenticates the request names the actor, anything else the caller sends is ignored. The plugin
+/// only has the shared password, so this names the credential used, not a person.
+#[derive(Clone)]
+pub struct Actor(pub String);
+
+impl Actor {
+    /// Changes delivered by the experiences server.
+    pub fn webhook() -> Self {
+        Actor("webhook".to_string())
+    }
+
+    fn hashed(key: &AuditKey, kind: &str, secret: &str) -> Self {
+        let mut mac = Hmac::<Sha256>::new_from_slice(&key.0).unwrap();
+        mac.update(secret.as_bytes());
+        let digest = hex::encode(mac.finalize().into_bytes());
+        Actor(format!("{}:{}", kind, &digest[..16]))
+    }
+
+    /// Whether the actor stands for the shared password rather than a single person.
+    fn is_shared(&self) -> bool {
+        self.0.starts_with("cookie:")
+    }
+}
+
+#[server_api::external::rocket::async_trait]
+impl<'r> FromRequest<'r> for Actor {
+    type Error = Infallible;
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let Some(key) = request.rocket().state::<AuditKey>() else {
+            return Outcome::Success(Actor("unknown".to_string()));
+        };
+        let actor = match request.cookies().get(AUTH_COOKIE) {
+            Some(cookie) if is_authenticated(request) => Actor::hashed(key, "cookie", cookie.value()),
+            _ => Actor("anonymous".to_string()),
+        };
+        Outcome::Success(actor)
+    }
+}
+
+/// One mutation of the timeline. Stored in the `audit` collection.
+#[derive(Serialize, Deserialize)]
+pub struct AuditEntry {
+    /// Milliseconds since the epoch.
+    pub timestamp: i64,
+    pub actor: String,
+    /// Set when `actor` is the shared password, which everyone with access uses. No per-person identity exists.
+    #[serde(default)]
+    pub shared_identity: bool,
+    pub action: String,
+    pub request_id: String,
+    /// Experience or other object the action changed.
+    #[serde(default)]
+    pub target: Option<String>,
+    #[serde(default)]
+    pub before: Option<Document>,
+    #[serde(default)]
+    pub after: Option<Document>,
+    /// Status and body of the response, including errors returned by the experiences server.
+    pub status: u16,
+    #[serde(default)]
+    pub response: Option<Bson>,
+}
+
+impl AuditEntry {
+    pub fn new(actor: &Actor, action: &str, request_id: &RequestId) -> Self {
+        AuditEntry {
+            timestamp: Utc::now().timestamp_millis(),
+            actor: actor.0.clone(),
+            shared_identity: actor.is_shared(),
+            action: action.to_string(),
+            request_id: request_id.0.clone(),
+            target: None,
+            before: None,
+            after: None,
+            status: Status::Ok.code,
+            response: None,
+        }
+    }
+
+    pub fn target(mut self, target: Option<&str>) -> Self {
+        self.target = target.map(|v| v.to_string());
+        self
+    }
+
+    pub fn before(mut self, state: Option<Document>) -> Self {
+        self.before = state;
+        self
+    }
+
+    pub fn after(mut self, state: Option<Document>) -> Self {
+        self.after = state;
+        self
+    }
+
+    pub fn result<T: Serialize>(mut self, result: &Result<T, (Status, APIError)>) -> Self {
+        let (status, response) = match result {
+            Ok(v) => (Status::Ok, bson::to_bson(v)),
+            Err((status, e)) => (*status, bson::to_bson(e)),
+        };
+        self.status = status.code;
+        self.response = response.ok();
+        self
+    }
+}
+
+/// Index for listing the log newest first.
+pub(crate) async fn ensure_indexes(
+    database: &Database,
+) -> Result<(), server_api::external::mongodb::error::Error> {
+    plugin_collection::<AuditEntry>(database, AUDIT_COLLECTION)
+        .create_index(
+            IndexModel::builder()
+                .keys(doc! {"timestamp": -1, "_id": -1})
+                .build(),
+            None,
+        )
+        .await?;
+    Ok(())
+}
+
+/// Stored timeline event of an experience, `None` if it does not exist.
+pub(crate) async fn snapshot(database: &Database, id: &str) -> Option<Document> {
+    database
+        .get_events::<DatabaseExperience>()
+        .clone_with_type::<Document>()
+        .find_one(experience_filter(id), None)
+        .await
+        .unwrap_or_else(|e| {
+            tracing::warn!(experience_id = %id, "unable to read experience for the audit log: {}", e);
+            None
+        })
+}
+
+/// Appends an entry to the audit log. A failing write is logged but never fails the audited request.
+pub(crate) async fn record(database: &Database, entry: AuditEntry) {
+    if let Err(e) = plugin_collection::<AuditEntry>(database, AUDIT_COLLECTION)
+        .insert_one(&entry, None)
+        .await
+    {
+        tracing::error!(action = %entry.action, request_id = %entry.request_id, "unable to write audit entry: {}", e);
+    }
+}
+
+/// Records an action that created an experience, with the stored experience as the after state. A replay of an earlier
+/// request with the same idempotency key changed nothing, so it is recorded as `replay` without an after state.
+pub(crate) async fn record_created(
+    services: &Services<'_>,
+    actor: &Actor,
+    action: &str,
+    request_id: &RequestId,
+    before: Option<Document>,
+    result: &Result<Created, (Status, APIError)>,
+) {
+    let (action, id, after) = match result {
+        Ok(Created::New(id)) => (action, Some(id), snapshot(services.database, id).await),
+        Ok(Created::Existing(id)) => ("replay", Some(id), None),
+        Err(_) => (action, None, None),
+    };
+    let entry = AuditEntry::new(actor, action, request_id)
+        .target(id.map(|v| v.as_str()))
+        .before(before)
+        .after(after)
+        .result(result);
+    record(services.database, entry).await;
+}
+
+#[derive(Serialize)]
+pub struct AuditPage {
+    pub entries: Vec<AuditEntry>,
+    pub page: u64,
+    pub per_page: u64,
+    pub total: u64,
+}
+
+/// Entries of the audit log, newest first. Pages start at 0.
+#[get("/audit?<page>&<per_page>")]
+pub async fn list_audit(
+    page: Option<u64>,
+    per_page: Option<u64>,
+    cookies: &CookieJar<'_>,
+    services: Services<'_>,
+) -> Custom<Json<APIResult<AuditPage>>> {
+    respond(
+        async {
+            services.authenticate(cookies)?;
+            let page = page.unwrap_or(0);
+            if page > MAX_PAGE {
+                return Err((
+                    Status::BadRequest,
+                    APIError::Custom(format!("The page has to be at most {}", MAX_PAGE)),
+                ));
+            }
+            let per_page = per_page.unwrap_or(50).clamp(1, MAX_PER_PAGE);
+            let collection = plugin_collection::<AuditEntry>(services.database, AUDIT_COLLECTION);
+            let total = collection
+                .count_documents(None, None)
+                .await
+                .map_err(|e| (Status::InternalServerError, e.into()))?;
+            let options = FindOptions::builder()
+                .sort(doc! {"timestamp": -1, "_id": -1})
+                .skip(page * per_page)
+                .limit(per_page as i64)
+                .projection(doc! {"_id": 0})
+                .build();
+            let entries = collection
+                .find(None, options)
+                .await
+                .map_err(|e| (Status::InternalServerError, e.into()))?
+                .try_collect()
+                .await
+                .map_err(|e| (Status::InternalServerError, e.into()))?;
+            Ok(AuditPage {
+                entries,
+                page,
+                per_page,
+                total,
+            })
+        }
+        .await,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::test_config,
+        server_api::external::rocket::{
+            http::{Cookie, Header},
+            local::asynchronous::Client,
+            routes,
+        },
+    };
+
+    #[get("/actor")]
+    fn actor(actor: Actor) -> String {
+        actor.0
+    }
+
+    async fn client() -> Client {
+        let rocket = server_api::external::rocket::build()
+            .manage(test_config("secret"))
+            .manage(AuditKey::new(Some("audit key")))
+            .mount("/", routes![actor]);
+        Client::untracked(rocket).await.unwrap()
+    }
+
+    #[server_api::external::rocket::async_test]
+    async fn bearer_header_does_not_change_the_actor() {
+        let client = client().await;
+        let expected = Actor::hashed(&AuditKey::new(Some("audit key")), "cookie", "secret").0;
+        let with_cookie = client
+            .get("/actor")
+            .cookie(Cookie::new(AUTH_COOKIE, "secret"))
+            .dispatch()
+            .await;
+        assert_eq!(with_cookie.into_string().await.unwrap(), expected);
+        let with_bearer = client
+            .get("/actor")
+            .cookie(Cookie::new(AUTH_COOKIE, "secret"))
+            .header(Header::new("Authorization", "Bearer made-up"))
+            .dispatch()
+            .await;
+        assert_eq!(with_bearer.into_string().await.unwrap(), expected);
+    }
+
+    #[server_api::external::rocket::async_test]
+    async fn unauthenticated_requests_are_anonymous() {
+        let client = client().await;
+        let wrong_cookie = client
+            .get("/actor")
+            .cookie(Cookie::new(AUTH_COOKIE, "guess"))
+            .dispatch()
+            .await;
+        assert_eq!(wrong_cookie.into_string().await.unwrap(), "anonymous");
+        let bearer_only = client
+            .get("/actor")
+            .header(Header::new("Authorization", "Bearer made-up"))
+            .dispatch()
+            .await;
+        assert_eq!(bearer_only.into_string().await.unwrap(), "anonymous");
+    }
+}
@@ -0,0 +1,100 @@
+use {
+    crate::{
+        experience_filter,
+        experiences::{ExperiencesClient, RequestId, UpstreamError},
+        live::LiveUpdate,
+        DatabaseExperience, Services,
+    },
+    image::GenericImageView,
+    server_api::external::{mongodb::bson::doc, rocket::tokio::task},
+    std::collections::HashMap,
+};
+
+/// Accepts `#rrggbb` only, so the value can be put into a style attribute as is.
+pub fn is_valid_color(color: &str) -> bool {
+    color.len() == 7
+        && color.starts_with('#')
+        && color[1..].chars().all(|c| c.is_ascii_hexdigit())
+}
+
+/// An icon is a short piece of text, usually a single emoji.
+pub fn is_valid_icon(icon: &str) -> bool {
+    let count = icon.chars().count();
+    count > 0 && count <= 8 && !icon.chars().any(char::is_control)
+}
+
+/// Most common color of an image. Pixels are grouped into buckets of similar colors, the average of the biggest bucket wins.
+pub fn dominant_color(bytes: &[u8]) -> Option<String> {
+    let image = image::load_from_memory(bytes).ok()?.thumbnail(64, 64);
+    let mut buckets: HashMap<(u8, u8, u8), (u32, [u64; 3])> = HashMap::new();
+    for (_, _, pixel) in image.pixels() {
+        let [r, g, b, a] = pixel.0;
+        if a < 128 {
+            continue;
+        }
+        let bucket = buckets.entry((r >> 4, g >> 4, b >> 4)).or_default();
+        bucket.0 += 1;
+        bucket.1[0] += r as u64;
+        bucket.1[1] += g as u64;
+        bucket.1[2] += b as u64;
+    }
+    let (count, sum) = buckets.into_values().max_by_key(|(count, _)| *count)?;
+    let count = count as u64;
+    Some(format!(
+        "#{:02x}{:02x}{:02x}",
+        sum[0] / count,
+        sum[1] / count,
+        sum[2] / count
+    ))
+}
+
+/// Downloads the cover of an experience and returns its dominant color. `None` if the cover can not be decoded.
+/// Decoding runs on the blocking pool, a full size cover takes too long for the async executor.
+pub async fn cover_color(
+    experiences: &ExperiencesClient,
+    id: &str,
+    request_id: &RequestId,
+) -> Result<Option<String>, UpstreamError> {
+    let bytes = experiences
+        .get_bytes(
+            "cover",
+            &format!("/api/experience/{}/cover/big", id),
+            request_id,
+        )
+        .await?;
+    Ok(task::spawn_blocking(move || dominant_color(&bytes))
+        .await
+        .ok()
+        .flatten())
+}
+
+/// Derives the cover color of a stored experience again after its media changed, and sends the experience to live clients.
+/// Failures only skip the color, the media is there either way.
+pub(crate) async fn refresh(services: &Services<'_>, id: &str, request_id: &RequestId) {
+    let events = services.database.get_events::<DatabaseExperience>();
+    match cover_color(services.experiences, id, request_id).await {
+        Ok(Some(color)) => {
+            if let Err(e) = events
+                .update_one(
+                    experience_filter(id),
+                    doc! {"$set": {"event.cover_color": color}},
+                    None,
+                )
+                .await
+            {
+                tracing::warn!(experience_id = %id, "unable to store cover color: {}", e);
+            }
+        }
+        Ok(None) => {}
+        Err(e) => tracing::warn!(experience_id = %id, "unable to derive cover color: {}", e),
+    }
+    match events.find_one(experience_filter(id), None).await {
+        Ok(Some(stored)) => services.live_updates.send(LiveUpdate::Changed {
+            title: stored.event.name.clone(),
+            time: stored.timing,
+            data: stored.event.to_compressed(),
+        }),
+        Ok(None) => {}
+        Err(e) => tracing::warn!(experience_id = %id, "unable to reload experience: {}", e),
+    }
+}
@@ -0,0 +1,320 @@
+use {
+    crate::{is_authenticated, metrics::Metrics, ConfigData},
+    server_api::external::{
+        rocket::{
+            http::Status,
+            request::{FromRequest, Outcome},
+            Request,
+        },
+        url::Url,
+    },
+    std::sync::Arc,
+};
+
+/// Header the client sends with every mutating request, and its value. Browsers only let other sites set it after a
+/// CORS preflight, which this plugin never answers, so unlike `Origin` it can not be left out by privacy settings. The
+/// client has its own copy, a test there keeps the two equal.
+pub const REQUESTED_WITH: (&str, &str) = ("X-Requested-With", "timeline_plugin_experience");
+
+/// `scheme://host[:port]` of a URL, the form browsers send in `Origin`.
+fn origin_of(url: &Url) -> Option<String> {
+    let origin = url.origin();
+    origin.is_tuple().then(|| origin.ascii_serialization())
+}
+
+/// Host the request was sent to, as named in its `Host` header, and the scheme it arrived with.
+struct Target<'a> {
+    scheme: &'a str,
+    domain: &'a str,
+    port: Option<u16>,
+}
+
+/// Whether `origin` names the host the request was sent to, or one of the `allowed_origins`.
+fn is_allowed(target: Option<&Target<'_>>, allowed_origins: &[String], origin: &Url) -> bool {
+    let Some(serialized) = origin_of(origin) else {
+        return false;
+    };
+    if allowed_origins
+        .iter()
+        .filter_map(|v| Url::parse(v).ok())
+        .any(|v| origin_of(&v).as_deref() == Some(serialized.as_str()))
+    {
+        return true;
+    }
+    let Some(target) = target else {
+        return false;
+    };
+    // A `Host` without port means the default port of the scheme.
+    let port = target.port.or(match target.scheme {
+        "https" => Some(443),
+        "http" => Some(80),
+        _ => None,
+    });
+    origin.scheme() == target.scheme
+        && origin.host_str() == Some(target.domain)
+        && origin.port_or_known_default() == port
+}
+
+/// Scheme the browser used. Taken from `X-Forwarded-Proto` behind a proxy terminating TLS.
+fn scheme_of<'a>(request: &'a Request<'_>) -> &'a str {
+    match request.headers().get_one("X-Forwarded-Proto") {
+        Some(v) => v.split(',').next().unwrap_or(v).trim(),
+        None if request.rocket().config().tls_enabled() => "https",
+        None => "http",
+    }
+}
+
+/// Rejects requests sent by other sites with 403 Forbidden. Browsers attach the auth cookie to cross-site requests too,
+/// so a request the cookie authenticates has to send [`REQUESTED_WITH`], and if it names where it comes from in `Origin`
+/// or `Referer`, that has to be this host. Other requests pass, the authentication turns them down anyway.
+///
+/// `Origin` alone is not enough: browsers and extensions may leave it out, and older browsers do not send it at all.
+/// The header covers those, at the price that only the plugin's own client, which sets it, can call these routes with
+/// the cookie.
+pub struct SameOrigin;
+
+#[server_api::external::rocket::async_trait]
+impl<'r> FromRequest<'r> for SameOrigin {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let Some(config) = request.rocket().state::<ConfigData>() else {
+            return Outcome::Error((Status::InternalServerError, ()));
+        };
+        if !is_authenticated(request) {
+            return Outcome::Success(SameOrigin);
+        }
+        let header = request.headers().get_one(REQUESTED_WITH.0);
+        let origin = request
+            .headers()
+            .get_one("Origin")
+            .or_else(|| request.headers().get_one("Referer"));
+        let target = request.host().map(|host| Target {
+            scheme: scheme_of(request),
+            domain: host.domain().as_str(),
+            port: host.port(),
+        });
+        // `Origin: null` of sandboxed or privacy sensitive contexts fails to parse and is rejected.
+        let origin_allowed = match origin {
+            Some(v) => Url::parse(v).is_ok_and(|v| is_allowed(target.as_ref(), &config.allowed_origins, &v)),
+            None => true,
+        };
+        if header == Some(REQUESTED_WITH.1) && origin_allowed {
+            return Outcome::Success(SameOrigin);
+        }
+        tracing::warn!(
+            origin = origin.unwrap_or("none"),
+            requested_with = header.unwrap_or("none"),
+            "rejected cross-site request"
+        );
+        if let Some(metrics) = request.rocket().state::<Arc<Metrics>>() {
+            metrics.failure("cross_site");
+        }
+        Outcome::Error((Status::Forbidden, ()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::{test_config, AUTH_COOKIE},
+        server_api::external::{
+            rocket::{
+                http::{Cookie, Header},
+                local::asynchronous::Client,
+                post, routes,
+            },
+            toml,
+        },
+    };
+
+    const HOST: Target<'static> = Target {
+        scheme: "https",
+        domain: "timeline.example",
+        port: None,
+    };
+
+    fn allowed(target: Option<&Target<'_>>, allowed_origins: &[&str], origin: &str) -> bool {
+        let allowed_origins = allowed_origins.iter().map(|v| v.to_string()).collect::<Vec<_>>();
+        Url::parse(origin).is_ok_and(|v| is_allowed(target, &allowed_origins, &v))
+    }
+
+    #[test]
+    fn origin_drops_path_and_default_port() {
+        let url = Url::parse("https://timeline.example:443/api/plugin?x=1").unwrap();
+        assert_eq!(origin_of(&url).as_deref(), Some("https://timeline.example"));
+        let url = Url::parse("http://timeline.example:8000/").unwrap();
+        assert_eq!(origin_of(&url).as_deref(), Some("http://timeline.example:8000"));
+        assert_eq!(origin_of(&Url::parse("data:text/plain,x").unwrap()), None);
+    }
+
+    #[test]
+    fn same_host_without_port() {
+        assert!(allowed(Some(&HOST), &[], "https://timeline.example"));
+        assert!(allowed(Some(&HOST), &[], "https://timeline.example:443"));
+        assert!(!allowed(Some(&HOST), &[], "https://timeline.example:8443"));
+    }
+
+    #[test]
+    fn same_host_with_port() {
+        let target = Target {
+            port: Some(8000),
+            ..HOST
+        };
+        assert!(allowed(Some(&target), &[], "https://timeline.example:8000"));
+        assert!(!allowed(Some(&target), &[], "https://timeline.example"));
+    }
+
+    #[test]
+    fn scheme_has_to_match() {
+        assert!(!allowed(Some(&HOST), &[], "http://timeline.example"));
+        let target = Target {
+            scheme: "http",
+            port: Some(80),
+            ..HOST
+        };
+        assert!(!allowed(Some(&target), &[], "https://timeline.example"));
+    }
+
+    #[test]
+    fn foreign_origin() {
+        assert!(!allowed(Some(&HOST), &[], "https://evil.example"));
+        assert!(!allowed(Some(&HOST), &[], "https://timeline.example.evil.example"));
+        assert!(!allowed(None, &[], "https://timeline.example"));
+    }
+
+    #[test]
+    fn null_origin() {
+        assert!(!allowed(Some(&HOST), &[], "null"));
+        assert!(!allowed(Some(&HOST), &["null"], "null"));
+    }
+
+    #[test]
+    fn referer_is_compared_by_its_origin() {
+        assert!(allowed(Some(&HOST), &[], "https://timeline.example/some/page?x=1"));
+        assert!(!allowed(Some(&HOST), &[], "https://evil.example/timeline.example"));
+    }
+
+    #[test]
+    fn configured_origins() {
+        let origins = ["https://proxy.example", "http://localhost:3000/"];
+        assert!(allowed(Some(&HOST), &origins, "https://proxy.example"));
+        assert!(allowed(None, &origins, "http://localhost:3000"));
+        assert!(!allowed(Some(&HOST), &origins, "http://proxy.example"));
+        assert!(!allowed(Some(&HOST), &origins, "http://localhost:3001"));
+    }
+
+    #[post("/guarded")]
+    fn guarded(_same_origin: SameOrigin) -> &'static str {
+        "ok"
+    }
+
+    async fn client() -> Client {
+        let config: ConfigData = toml::from_str(
+            r#"
+                password = "secret"
+                url = "http://experiences.example"
+            "#,
+        )
+        .unwrap();
+        let rocket = server_api::external::rocket::build()
+            .manage(config)
+            .manage(test_config("secret"))
+            .mount("/", routes![guarded]);
+        Client::untracked(rocket).await.unwrap()
+    }
+
+    fn same_site_headers() -> [Header<'static>; 2] {
+        [
+            Header::new("Host", "localhost:8000"),
+            Header::new(REQUESTED_WITH.0, REQUESTED_WITH.1),
+        ]
+    }
+
+    #[server_api::external::rocket::async_test]
+    async fn cookie_from_foreign_origin_is_forbidden() {
+        let client = client().await;
+        let [host, requested_with] = same_site_headers();
+        let response = client
+            .post("/guarded")
+            .header(host)
+            .header(requested_with)
+            .header(Header::new("Origin", "https://evil.example"))
+            .cookie(Cookie::new(AUTH_COOKIE, "secret"))
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::Forbidden);
+    }
+
+    #[server_api::external::rocket::async_test]
+    async fn cookie_without_requested_with_is_forbidden() {
+        let client = client().await;
+        let response = client
+            .post("/guarded")
+            .header(Header::new("Host", "localhost:8000"))
+            .header(Header::new("Origin", "http://localhost:8000"))
+            .cookie(Cookie::new(AUTH_COOKIE, "secret"))
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::Forbidden);
+    }
+
+    #[server_api::external::rocket::async_test]
+    async fn cookie_from_own_client_passes() {
+        let client = client().await;
+        let [host, requested_with] = same_site_headers();
+        let response = client
+            .post("/guarded")
+            .header(host)
+            .header(requested_with)
+            .header(Header::new("Origin", "http://localhost:8000"))
+            .cookie(Cookie::new(AUTH_COOKIE, "secret"))
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::Ok);
+    }
+
+    #[server_api::external::rocket::async_test]
+    async fn referer_stands_in_for_missing_origin() {
+        let client = client().await;
+        for (referer, status) in [
+            ("http://localhost:8000/timeline", Status::Ok),
+            ("https://evil.example/page", Status::Forbidden),
+        ] {
+            let [host, requested_with] = same_site_headers();
+            let response = client
+                .post("/guarded")
+                .header(host)
+                .header(requested_with)
+                .header(Header::new("Referer", referer))
+                .cookie(Cookie::new(AUTH_COOKIE, "secret"))
+                .dispatch()
+                .await;
+            assert_eq!(response.status(), status, "{}", referer);
+        }
+    }
+
+    #[server_api::external::rocket::async_test]
+    async fn cookie_that_does_not_authenticate_is_left_to_the_route() {
+        let client = client().await;
+        let response = client
+            .post("/guarded")
+            .header(Header::new("Origin", "https://evil.example"))
+            .cookie(Cookie::new(AUTH_COOKIE, "guess"))
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::Ok);
+    }
+
+    #[server_api::external::rocket::async_test]
+    async fn requests_without_cookie_pass() {
+        let client = client().await;
+        let response = client
+            .post("/guarded")
+            .header(Header::new("Origin", "https://evil.example"))
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::Ok);
+    }
+}
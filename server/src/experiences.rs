@@ -0,0 +1,265 @@
+use {
+    crate::{
+        i18n::{Locale, Message},
+        metrics::Metrics,
+    },
+    serde::{de::DeserializeOwned, Serialize},
+    server_api::external::{
+        rocket::{
+            request::{FromRequest, Outcome},
+            tokio::sync::{Semaphore, SemaphorePermit},
+            Request,
+        },
+        types::{
+            api::{APIError, APIResult},
+            external::{reqwest, serde_json},
+        },
+        url::Url,
+    },
+    std::{convert::Infallible, fmt, future::Future, sync::Arc, time::Instant},
+    tracing::{field, Instrument},
+    uuid::Uuid,
+};
+
+pub const REQUEST_ID_HEADER: &str = "X-Request-Id";
+
+/// Id of the incoming request. Taken from the `X-Request-Id` header when the caller sent a sane one, generated otherwise. It is forwarded to the experiences server.
+pub struct RequestId(pub String);
+
+#[server_api::external::rocket::async_trait]
+impl<'r> FromRequest<'r> for RequestId {
+    type Error = Infallible;
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let id = request
+            .headers()
+            .get_one(REQUEST_ID_HEADER)
+            .filter(|v| {
+                !v.is_empty()
+                    && v.len() <= 128
+                    && v.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+            })
+            .map(|v| v.to_string())
+            .unwrap_or_else(|| Uuid::new_v4().to_string());
+        Outcome::Success(RequestId(id))
+    }
+}
+
+impl fmt::Display for RequestId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+pub enum UpstreamError {
+    Send(String),
+    Read(String),
+    Decode(String),
+    Remote(APIError),
+}
+
+impl UpstreamError {
+    /// Label used for the failure metric.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            UpstreamError::Send(_) => "upstream_send",
+            UpstreamError::Read(_) => "upstream_read",
+            UpstreamError::Decode(_) => "upstream_decode",
+            UpstreamError::Remote(_) => "upstream_remote",
+        }
+    }
+
+    pub fn localized(&self, locale: Locale) -> String {
+        match self {
+            UpstreamError::Send(e) => locale.format(Message::UnableToSendRequest, e),
+            UpstreamError::Read(e) | UpstreamError::Decode(e) => {
+                locale.format(Message::UnableToReadResponse, e)
+            }
+            UpstreamError::Remote(e) => {
+                locale.format(Message::ExperiencesServerError, &e.to_string())
+            }
+        }
+    }
+
+    pub fn to_api_error(&self, locale: Locale) -> APIError {
+        match self {
+            UpstreamError::Send(_) | UpstreamError::Read(_) => APIError::Custom(self.localized(locale)),
+            UpstreamError::Decode(_) | UpstreamError::Remote(_) => APIError::SerdeJsonError(self.localized(locale)),
+        }
+    }
+}
+
+impl fmt::Display for UpstreamError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.localized(Locale::En))
+    }
+}
+
+/// Talks to the experiences server. Every call runs inside a `experiences_request` span and is recorded in the upstream latency histogram.
+pub struct ExperiencesClient {
+    client: reqwest::Client,
+    url: Url,
+    password: String,
+    metrics: Arc<Metrics>,
+    /// Caps the requests in flight, so a burst of plugin requests can not flood the server.
+    permits: Semaphore,
+}
+
+impl ExperiencesClient {
+    /// `max_concurrent` of 0 does not limit the requests in flight.
+    pub fn new(url: Url, password: String, max_concurrent: usize, metrics: Arc<Metrics>) -> Self {
+        let permits = match max_concurrent {
+            0 => Semaphore::MAX_PERMITS,
+            v => v.min(Semaphore::MAX_PERMITS),
+        };
+        ExperiencesClient {
+            client: reqwest::Client::new(),
+            url,
+            password,
+            metrics,
+            permits: Semaphore::new(permits),
+        }
+    }
+
+    /// Waits until fewer than `max_concurrent` requests are in flight. The permit is given back when dropped.
+    async fn permit(&self) -> SemaphorePermit<'_> {
+        // The semaphore is never closed, so acquiring can not fail.
+        self.permits.acquire().await.unwrap()
+    }
+
+    /// `GET` or `POST` of `path`, sent with the server password.
+    fn authorized(&self, method: reqwest::Method, path: &str) -> reqwest::RequestBuilder {
+        self.client
+            .request(method, self.url.join(path).unwrap())
+            .header(reqwest::header::COOKIE, format!("pwd={}", self.password))
+    }
+
+    /// Sends `request` inside an `experiences_request` span once a permit is free, records its latency and status, and
+    /// hands the response to `read`. The permit is held until `read` is done with the body.
+    async fn send<T, F, Fut>(
+        &self,
+        endpoint: &str,
+        path: &str,
+        request: reqwest::RequestBuilder,
+        request_id: &RequestId,
+        read: F,
+    ) -> Result<T, UpstreamError>
+    where
+        F: FnOnce(reqwest::Response) -> Fut,
+        Fut: Future<Output = Result<T, UpstreamError>>,
+    {
+        let span = tracing::info_span!(
+            "experiences_request",
+            endpoint,
+            path,
+            request_id = %request_id,
+            status = field::Empty,
+            latency_ms = field::Empty,
+        );
+        async {
+            let _permit = self.permit().await;
+            let start = Instant::now();
+            let result = request.header(REQUEST_ID_HEADER, &request_id.0).send().await;
+            let elapsed = start.elapsed();
+            self.metrics
+                .upstream_latency
+                .with_label_values(&[endpoint])
+                .observe(elapsed.as_secs_f64());
+            let span = tracing::Span::current();
+            span.record("latency_ms", elapsed.as_millis() as u64);
+            let response = result.map_err(|e| UpstreamError::Send(e.to_string()))?;
+            span.record("status", response.status().as_u16());
+            read(response).await
+        }
+        .instrument(span)
+        .await
+    }
+
+    /// Sends a plain `GET` to the server root. Any HTTP answer counts as reachable.
+    pub async fn ping(&self, request_id: &RequestId) -> Result<(), UpstreamError> {
+        let request = self.client.get(self.url.clone());
+        self.send("ping", "/", request, request_id, |_| async { Ok(()) })
+            .await
+    }
+
+    /// Downloads a binary resource, for example a cover.
+    pub async fn get_bytes(
+        &self,
+        endpoint: &str,
+        path: &str,
+        request_id: &RequestId,
+    ) -> Result<Vec<u8>, UpstreamError> {
+        let request = self.authorized(reqwest::Method::GET, path);
+        self.send(endpoint, path, request, request_id, |response| async move {
+            let response = response
+                .error_for_status()
+                .map_err(|e| UpstreamError::Read(e.to_string()))?;
+            Ok(response
+                .bytes()
+                .await
+                .map_err(|e| UpstreamError::Read(e.to_string()))?
+                .to_vec())
+        })
+        .await
+    }
+
+    /// Status of a `GET`, without treating error statuses as failures. Used to tell a rejected password from other answers.
+    pub async fn get_status(
+        &self,
+        endpoint: &str,
+        path: &str,
+        request_id: &RequestId,
+    ) -> Result<reqwest::StatusCode, UpstreamError> {
+        let request = self.authorized(reqwest::Method::GET, path);
+        self.send(endpoint, path, request, request_id, |response| async move {
+            Ok(response.status())
+        })
+        .await
+    }
+
+    /// `GET` of a JSON route answering with an `APIResult`.
+    pub async fn get<T: DeserializeOwned>(
+        &self,
+        endpoint: &str,
+        path: &str,
+        request_id: &RequestId,
+    ) -> Result<T, UpstreamError> {
+        let bytes = self.get_bytes(endpoint, path, request_id).await?;
+        serde_json::from_slice::<APIResult<T>>(&bytes)
+            .map_err(|e| UpstreamError::Decode(e.to_string()))?
+            .map_err(UpstreamError::Remote)
+    }
+
+    pub async fn post<B: Serialize, T: DeserializeOwned>(
+        &self,
+        endpoint: &str,
+        path: &str,
+        body: &B,
+        request_id: &RequestId,
+    ) -> Result<T, UpstreamError> {
+        self.post_body(endpoint, path, serde_json::to_string(body).unwrap().into(), request_id)
+            .await
+    }
+
+    /// Posts a raw body, for example a file streamed from disk.
+    pub async fn post_body<T: DeserializeOwned>(
+        &self,
+        endpoint: &str,
+        path: &str,
+        body: reqwest::Body,
+        request_id: &RequestId,
+    ) -> Result<T, UpstreamError> {
+        let request = self.authorized(reqwest::Method::POST, path).body(body);
+        self.send(endpoint, path, request, request_id, |response| async move {
+            let text = response
+                .text()
+                .await
+                .map_err(|e| UpstreamError::Read(e.to_string()))?;
+            serde_json::from_str::<APIResult<T>>(&text)
+                .map_err(|e| UpstreamError::Decode(e.to_string()))?
+                .map_err(UpstreamError::Remote)
+        })
+        .await
+        .inspect_err(|e| tracing::warn!(kind = e.kind(), "experiences server request failed"))
+    }
+}
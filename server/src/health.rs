@@ -0,0 +1,166 @@
+use {
+    crate::{
+        experiences::{ExperiencesClient, RequestId},
+        DatabaseExperience,
+    },
+    serde::{Deserialize, Serialize},
+    server_api::{
+        config::Config,
+        db::Database,
+        external::{
+            mongodb::{
+                bson::{doc, Document},
+                options::FindOneOptions,
+            },
+            rocket::{
+                get,
+                http::{CookieJar, Status},
+                response::status::{self, Custom},
+                serde::json::Json,
+                State,
+            },
+            types::{
+                api::{APIError, APIResult},
+                available_plugins::AvailablePlugins,
+            },
+        },
+        web::auth,
+    },
+    std::{sync::Arc, time::Instant},
+    tracing::Instrument,
+};
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum HealthStatus {
+    Ok,
+    Down,
+    /// The check had nothing to test yet.
+    Unknown,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ComponentHealth {
+    pub status: HealthStatus,
+    pub latency_ms: u64,
+    pub message: Option<String>,
+}
+
+impl ComponentHealth {
+    fn measure<T, E: ToString>(start: Instant, result: Result<T, E>) -> Self {
+        let latency_ms = start.elapsed().as_millis() as u64;
+        match result {
+            Ok(_) => ComponentHealth {
+                status: HealthStatus::Ok,
+                latency_ms,
+                message: None,
+            },
+            Err(e) => ComponentHealth {
+                status: HealthStatus::Down,
+                latency_ms,
+                message: Some(e.to_string()),
+            },
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct HealthReport {
+    /// Whether the experiences server answers at all.
+    pub reachability: ComponentHealth,
+    /// Whether the configured password is accepted by the experiences server.
+    pub authentication: ComponentHealth,
+    pub database: ComponentHealth,
+}
+
+impl HealthReport {
+    pub fn is_healthy(&self) -> bool {
+        [&self.reachability, &self.authentication, &self.database]
+            .iter()
+            .all(|v| v.status != HealthStatus::Down)
+    }
+}
+
+/// Requests the cover of the most recently stored experience, the same authenticated route the timeline loads covers
+/// from. Only a successful answer proves the password was accepted. `false` if there is no experience to request yet.
+async fn check_password(
+    database: &Database,
+    experiences: &ExperiencesClient,
+    request_id: &RequestId,
+) -> Result<bool, String> {
+    let latest = database
+        .get_events::<DatabaseExperience>()
+        .find_one(
+            Database::generate_find_plugin_filter(AvailablePlugins::timeline_plugin_experience),
+            FindOneOptions::builder().sort(doc! {"_id": -1}).build(),
+        )
+        .await
+        .map_err(|e| e.to_string())?;
+    let Some(latest) = latest else {
+        return Ok(false);
+    };
+    let status = experiences
+        .get_status(
+            "auth",
+            &format!("/api/experience/{}/cover/big", latest.event.id),
+            request_id,
+        )
+        .await
+        .map_err(|e| e.to_string())?;
+    if status.is_success() {
+        Ok(true)
+    } else {
+        Err(format!("The experiences server answered {}", status))
+    }
+}
+
+/// Checks the experiences server and the database. Answers with `503` if any of them is down.
+#[get("/health")]
+pub async fn health(
+    cookies: &CookieJar<'_>,
+    config: &State<Config>,
+    database: &State<Arc<Database>>,
+    experiences: &State<ExperiencesClient>,
+    request_id: RequestId,
+) -> Custom<Json<APIResult<HealthReport>>> {
+    if auth(cookies, config).is_err() {
+        return status::Custom(
+            Status::Unauthorized,
+            Json(Err(APIError::AuthenticationError)),
+        );
+    }
+
+    let start = Instant::now();
+    let reachability = ComponentHealth::measure(start, experiences.ping(&request_id).await);
+
+    let start = Instant::now();
+    let authentication = match check_password(database, experiences, &request_id).await {
+        Ok(false) => ComponentHealth {
+            status: HealthStatus::Unknown,
+            latency_ms: start.elapsed().as_millis() as u64,
+            message: Some("There is no experience to request a cover of yet".to_string()),
+        },
+        result => ComponentHealth::measure(start, result),
+    };
+
+    let start = Instant::now();
+    let database = ComponentHealth::measure(
+        start,
+        database
+            .get_events::<Document>()
+            .estimated_document_count(None)
+            .instrument(tracing::info_span!("database", operation = "health"))
+            .await,
+    );
+
+    let report = HealthReport {
+        reachability,
+        authentication,
+        database,
+    };
+    let status = if report.is_healthy() {
+        Status::Ok
+    } else {
+        Status::ServiceUnavailable
+    };
+    status::Custom(status, Json(Ok(report)))
+}
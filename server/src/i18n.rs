@@ -0,0 +1,64 @@
+use serde::Deserialize;
+
+#[derive(Deserialize, Clone, Copy, Default, PartialEq, Eq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum Locale {
+    #[default]
+    En,
+    De,
+}
+
+#[derive(Clone, Copy)]
+pub enum Message {
+    CreateExperience,
+    UnableToSendRequest,
+    UnableToReadResponse,
+    ExperiencesServerError,
+    SuggestedActivity,
+    SuggestedTrip,
+    TooManyRequests,
+}
+
+impl Locale {
+    /// Looks up `message` in this locale's catalog.
+    pub fn translate(self, message: Message) -> &'static str {
+        match self {
+            Locale::En => english(message),
+            Locale::De => german(message),
+        }
+    }
+
+    /// Translates a message with a single `{}` placeholder.
+    pub fn format(self, message: Message, value: &str) -> String {
+        self.translate(message).replacen("{}", value, 1)
+    }
+}
+
+fn english(message: Message) -> &'static str {
+    match message {
+        Message::CreateExperience => "Create Experience",
+        Message::UnableToSendRequest => "Unable to send request to experiences server: {}",
+        Message::UnableToReadResponse => "Unable to read response from experiences server: {}",
+        Message::ExperiencesServerError => "The experiences server reported an error: {}",
+        Message::SuggestedActivity => "Activity on {}",
+        Message::SuggestedTrip => "Trip on {}",
+        Message::TooManyRequests => "Too many requests, try again in {} seconds",
+    }
+}
+
+/// Exhaustive like `english`, so a message without German text does not compile.
+fn german(message: Message) -> &'static str {
+    match message {
+        Message::CreateExperience => "Erlebnis erstellen",
+        Message::UnableToSendRequest => {
+            "Anfrage an den Erlebnis-Server konnte nicht gesendet werden: {}"
+        }
+        Message::UnableToReadResponse => {
+            "Antwort des Erlebnis-Servers konnte nicht gelesen werden: {}"
+        }
+        Message::ExperiencesServerError => "Der Erlebnis-Server hat einen Fehler gemeldet: {}",
+        Message::SuggestedActivity => "Aktivität am {}",
+        Message::SuggestedTrip => "Ausflug am {}",
+        Message::TooManyRequests => "Zu viele Anfragen, bitte in {} Sekunden erneut versuchen",
+    }
+}
@@ -1,23 +1,71 @@
+mod analyzer;
+mod audit;
+mod color;
+mod csrf;
+mod experiences;
+mod health;
+mod i18n;
+mod live;
+mod media;
+mod media_date;
+mod memories;
+mod merge;
+mod metrics;
+mod rate_limit;
+mod series;
+mod sources;
+mod stats;
+mod suggest;
+mod templates;
+mod timezone;
+mod upload;
+mod webhook;
+
+pub use i18n::Locale;
+
 use {
+    crate::{
+        analyzer::SuggestionConfig,
+        audit::{Actor, AuditKey},
+        csrf::SameOrigin,
+        experiences::{ExperiencesClient, RequestId},
+        i18n::Message,
+        live::{LiveUpdate, LiveUpdates},
+        memories::MemoryConfig,
+        metrics::Metrics,
+        rate_limit::{RateLimitConfig, RateLimited, RateLimiter},
+        series::{Series, SeriesBadge},
+        sources::EventReference,
+        templates::ExperienceTemplate,
+        timezone::hourly_slots,
+    },
+    chrono_tz::Tz,
     server_api::{web::auth, db::{Event, Database}, plugin::PluginData},
-    server_api::external::types::external::chrono::TimeDelta,
+    server_api::external::types::external::chrono::{DateTime, Utc},
+    server_api::external::mongodb::{
+        bson::{self, doc, Document},
+        error::{ErrorKind, WriteFailure},
+        options::{FindOptions, IndexOptions},
+        Collection, IndexModel,
+    },
     server_api::external::futures::{self, StreamExt},
     server_api::external::rocket::{
         http::Status,
         post,
+        request::{FromRequest, Outcome},
         response::status::{self, Custom},
-        routes, Build, Rocket,
+        catchers, routes, Build, Request, Rocket,
     },
     serde::Deserialize,
     server_api::external::types::{
         api::{APIError, APIResult, CompressedEvent},
-        timing::Timing,
+        timing::{TimeRange, Timing},
         available_plugins::AvailablePlugins
     },
     server_api::external::url::Url,
+    tracing::Instrument,
 };
 
-use server_api::external::types::external::reqwest;
 use serde::Serialize;
 
 use server_api::config::Config;
@@ -26,20 +74,94 @@ use server_api::external::rocket::serde::json::Json;
 use server_api::external::rocket::State;
 use server_api::external::types::external::serde_json;
 use server_api::plugin::PluginTrait;
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
 use server_api::external::toml;
 use server_api::external::types;
 
-#[derive(Serialize, Deserialize)]
+/// Path the timeline mounts the routes of this plugin at. Catchers are registered there, so they only answer the plugin's requests.
+const PLUGIN_BASE: &str = "/api/plugin/timeline_plugin_experience";
+
+#[derive(Serialize, Deserialize, Clone)]
 pub enum CompressedExperienceEvent {
-    Experience(String),
+    Experience {
+        id: String,
+        name: String,
+        color: Option<String>,
+        icon: Option<String>,
+        /// Experiences that are part of this one, ordered by start time.
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        children: Vec<ExperienceChild>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        series: Option<SeriesBadge>,
+        /// Number of timeline events of other plugins the experience was built from.
+        #[serde(default, skip_serializing_if = "is_zero")]
+        built_from: usize,
+    },
     Create(Timing),
+    /// A burst of activity without an experience, found by the background analyzer.
+    Suggestion {
+        id: String,
+        name: String,
+        time: Timing,
+        event_count: usize,
+    },
+    /// "On this day": an experience from the same calendar day `years` ago.
+    Memory {
+        id: String,
+        name: String,
+        years: u32,
+        color: Option<String>,
+        icon: Option<String>,
+    },
+}
+
+fn is_zero(v: &usize) -> bool {
+    *v == 0
+}
+
+/// An experience shown inside the card of its parent instead of on its own.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ExperienceChild {
+    pub id: String,
+    pub name: String,
+    pub time: Timing,
+    pub color: Option<String>,
+    pub icon: Option<String>,
 }
 
 #[derive(Serialize, Deserialize)]
 pub struct CreateExperienceRequest {
     pub name: String,
     pub time: Timing,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Chosen by the client. Repeating a request with the same key returns the experience created by the first one.
+    #[serde(default)]
+    pub idempotency_key: Option<String>,
+    /// `#rrggbb`. Derived from the cover when missing.
+    #[serde(default)]
+    pub color: Option<String>,
+    #[serde(default)]
+    pub icon: Option<String>,
+    /// Experience this one is part of, e.g. the holiday of a single day.
+    #[serde(default)]
+    pub parent_id: Option<String>,
+    /// Starts a new series with this experience as its first occurrence.
+    #[serde(default)]
+    pub series_name: Option<String>,
+    /// Set by `/create_from_events`, never read from a request body.
+    #[serde(skip)]
+    pub sources: Vec<EventReference>,
+}
+
+/// Body of the experiences server's create route.
+#[derive(Serialize)]
+struct UpstreamCreateExperience<'a> {
+    name: &'a str,
+    time: &'a Timing,
+    #[serde(skip_serializing_if = "<[String]>::is_empty")]
+    tags: &'a [String],
 }
 
 
@@ -47,6 +169,44 @@ pub struct CreateExperienceRequest {
 struct ConfigData {
     pub password: String,
     pub url: Url,
+    /// Shared secret used to verify deliveries to `/webhook`. Webhooks are disabled without it.
+    #[serde(default)]
+    pub webhook_secret: Option<String>,
+    /// Language of the texts generated by the server, `en` or `de`.
+    #[serde(default)]
+    pub locale: Locale,
+    #[serde(default)]
+    pub templates: Vec<ExperienceTemplate>,
+    /// IANA time zone, e.g. `Europe/Berlin`. The create slots start at its full hours.
+    #[serde(default = "timezone::default_timezone")]
+    pub timezone: Tz,
+    /// Largest photo or video accepted by `/upload`, in MiB.
+    #[serde(default = "upload::default_max_upload_mb")]
+    pub max_upload_mb: u64,
+    /// URL per plugin to download the media of one of its events from, `{id}` is replaced with the event id.
+    /// Used when creating an experience from other plugins' events.
+    #[serde(default)]
+    pub media_sources: HashMap<String, String>,
+    /// Enables the background analyzer suggesting experiences for bursts of activity.
+    #[serde(default)]
+    pub suggestions: Option<SuggestionConfig>,
+    /// Enables "on this day" memories of experiences from previous years.
+    #[serde(default)]
+    pub memories: Option<MemoryConfig>,
+    /// Limits of the mutating routes and of the concurrent requests to the experiences server.
+    #[serde(default)]
+    pub rate_limit: RateLimitConfig,
+    /// Origins besides the plugin's own host allowed to send mutating requests, e.g. `https://timeline.example.com`
+    /// behind a proxy that rewrites `Host`.
+    #[serde(default)]
+    pub allowed_origins: Vec<String>,
+    /// Lets Prometheus scrape `/metrics` with `Authorization: Bearer <metrics_token>`. Without it the route needs the auth cookie.
+    #[serde(default)]
+    pub metrics_token: Option<String>,
+    /// Key of the HMAC identifying actors in the audit log. Without it a random key is used, so the same credential
+    /// only gets the same actor until the plugin restarts.
+    #[serde(default)]
+    pub audit_secret: Option<String>,
 }
 
 pub struct Plugin {
@@ -58,6 +218,83 @@ pub struct Plugin {
 struct DatabaseExperience {
     name: String,
     id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    idempotency_key: Option<String>,
+    /// Chosen by the user, takes precedence over `cover_color`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    color: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    icon: Option<String>,
+    /// Dominant color of the cover. Derived after media is added through the plugin, and on `CoverChanged` webhooks for
+    /// media added elsewhere. A new experience has no cover yet, so it starts without one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    cover_color: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    tags: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    parent_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    series: Option<Series>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    sources: Vec<EventReference>,
+}
+
+impl DatabaseExperience {
+    fn color(&self) -> Option<String> {
+        self.color.clone().or_else(|| self.cover_color.clone())
+    }
+
+    fn to_compressed(&self) -> CompressedExperienceEvent {
+        self.to_compressed_with(Vec::new(), self.series.as_ref().map(|v| v.badge(None)))
+    }
+
+    fn to_compressed_with(
+        &self,
+        children: Vec<ExperienceChild>,
+        series: Option<SeriesBadge>,
+    ) -> CompressedExperienceEvent {
+        CompressedExperienceEvent::Experience {
+            id: self.id.clone(),
+            name: self.name.clone(),
+            color: self.color(),
+            icon: self.icon.clone(),
+            children,
+            series,
+            built_from: self.sources.len(),
+        }
+    }
+
+    fn to_child(&self, time: Timing) -> ExperienceChild {
+        ExperienceChild {
+            id: self.id.clone(),
+            name: self.name.clone(),
+            time,
+            color: self.color(),
+            icon: self.icon.clone(),
+        }
+    }
+}
+
+/// Collection owned by this plugin, stored next to the shared events collection.
+fn plugin_collection<T: Send + Sync>(database: &Database, name: &str) -> Collection<T> {
+    database
+        .get_database()
+        .collection(&format!("timeline_plugin_experience_{}", name))
+}
+
+/// Matches the timeline event of a single experience.
+fn experience_filter(id: &str) -> Document {
+    Database::combine_documents(
+        Database::generate_find_plugin_filter(AvailablePlugins::timeline_plugin_experience),
+        doc! {"id": id},
+    )
+}
+
+/// The only parts of a stored event needed to build its `CompressedEvent`.
+#[derive(Deserialize)]
+struct ExperienceProjection {
+    timing: Timing,
+    event: DatabaseExperience,
 }
 
 impl PluginTrait for Plugin {
@@ -70,6 +307,21 @@ impl PluginTrait for Plugin {
                 .clone().expect("Failed to init experiences plugin! No config was provided!")
         )
         .unwrap_or_else(|e| panic!("Unable to init experiences plugin! Provided config does not fit the requirements: {}", e));
+        templates::validate_config(&config.templates)
+            .unwrap_or_else(|e| panic!("Unable to init experiences plugin! Invalid {}", e));
+
+        ensure_indexes(&data.database.get_events::<DatabaseExperience>().clone_with_type())
+            .await
+            .unwrap_or_else(|e| panic!("Unable to init experiences plugin! Failed to create indexes: {}", e));
+        audit::ensure_indexes(&data.database)
+            .await
+            .unwrap_or_else(|e| panic!("Unable to init experiences plugin! Failed to create audit indexes: {}", e));
+        merge::ensure_indexes(&data.database)
+            .await
+            .unwrap_or_else(|e| panic!("Unable to init experiences plugin! Failed to create merge indexes: {}", e));
+        series::ensure_indexes(&data.database)
+            .await
+            .unwrap_or_else(|e| panic!("Unable to init experiences plugin! Failed to create series indexes: {}", e));
 
         Plugin {
             plugin_data: data,
@@ -88,11 +340,47 @@ impl PluginTrait for Plugin {
     where
         Self: Sized,
     {
-        routes![create_experience]
+        routes![
+            analyzer::accept_suggestion,
+            analyzer::dismiss_suggestion,
+            audit::list_audit,
+            create_experience,
+            health::health,
+            live::live,
+            media::add_media,
+            media::list_media,
+            merge::merge_experiences,
+            metrics::metrics,
+            series::create_next_occurrence,
+            sources::create_from_events,
+            stats::get_stats,
+            suggest::suggest,
+            templates::list_templates,
+            templates::save_template,
+            templates::create_from_template,
+            timezone::client_config,
+            upload::create_from_upload,
+            webhook::webhook
+        ]
     }
 
     fn rocket_build_access(&self, rocket: Rocket<Build>) -> Rocket<Build> {
-        rocket.manage(self.config.clone())
+        let metrics = Arc::new(Metrics::new());
+        rocket
+            .manage(self.config.clone())
+            .manage(ExperiencesClient::new(
+                self.config.url.clone(),
+                self.config.password.clone(),
+                self.config.rate_limit.max_concurrent_upstream,
+                metrics.clone(),
+            ))
+            .manage(metrics)
+            .manage(AuditKey::new(self.config.audit_secret.as_deref()))
+            .manage(LiveUpdates::new())
+            .manage(RateLimiter::new(self.config.rate_limit.clone()))
+            .attach(analyzer::fairing(&self.config))
+            .attach(rate_limit::fairing())
+            .register(PLUGIN_BASE, catchers![rate_limit::too_many_requests])
     }
 
     fn get_compressed_events(
@@ -104,110 +392,673 @@ impl PluginTrait for Plugin {
                 + Send,
         >,
     > {
-        let database = self.plugin_data.database.clone();
+        let events = self
+            .plugin_data
+            .database
+            .get_events::<DatabaseExperience>()
+            .clone_with_type();
         let query_range = query_range.clone();
-        let filter = Database::generate_range_filter(&query_range);
-        let plg_filter =
-            Database::generate_find_plugin_filter(AvailablePlugins::timeline_plugin_experience);
-        let filter = Database::combine_documents(filter, plg_filter);
-        Box::pin(async move {
-            let mut cursor = database
-                .get_events::<DatabaseExperience>()
-                .find(filter, None)
-                .await?;
-            let mut result = Vec::new();
-            while let Some(v) = cursor.next().await {
-                let t = v?;
-                result.push(CompressedEvent {
-                    title: t.event.name,
-                    time: t.timing,
-                    data: serde_json::to_value(CompressedExperienceEvent::Experience(t.event.id)).unwrap(),
-                })
+        let database = self.plugin_data.database.clone();
+        let locale = self.config.locale;
+        let timezone = self.config.timezone;
+        let suggestions = self.config.suggestions.is_some();
+        let memories = self.config.memories.clone();
+        Box::pin(
+            async move {
+                let mut result = find_compressed_events(&events, &query_range, locale, timezone).await?;
+                if suggestions {
+                    let suggestions = analyzer::find_suggestions(&database, &query_range).await?;
+                    result = merge_by_time(result, suggestions);
+                }
+                if let Some(memories) = memories {
+                    let memories =
+                        memories::find_memories(&events, &query_range, &memories, timezone).await?;
+                    result = merge_by_time(result, memories);
+                }
+                Ok(result)
             }
+            .instrument(tracing::info_span!("database", operation = "find_experiences")),
+        )
+    }
+}
+
+/// Creates the compound indexes used by `find_compressed_events`. Creating an index that already exists is a no-op, so this runs on every startup.
+pub async fn ensure_indexes(
+    events: &Collection<Document>,
+) -> Result<(), server_api::external::mongodb::error::Error> {
+    events
+        .create_indexes(
+            vec![
+                IndexModel::builder()
+                    .keys(doc! {"plugin": 1, "timing.Instant": 1})
+                    .build(),
+                IndexModel::builder()
+                    .keys(doc! {"plugin": 1, "timing.Range.start": 1, "timing.Range.end": 1})
+                    .build(),
+                IndexModel::builder()
+                    .keys(doc! {"plugin": 1, "event.parent_id": 1})
+                    .options(IndexOptions::builder().sparse(true).build())
+                    .build(),
+            ],
+            None,
+        )
+        .await?;
+
+    // Replaced by the unique index below. Dropping fails harmlessly once it is gone.
+    let _ = events.drop_index("plugin_1_event.idempotency_key_1", None).await;
+    events
+        .create_index(
+            IndexModel::builder()
+                .keys(doc! {"plugin": 1, "event.idempotency_key": 1})
+                .options(
+                    IndexOptions::builder()
+                        .name("idempotency_key_unique".to_string())
+                        .unique(true)
+                        .partial_filter_expression(doc! {"event.idempotency_key": {"$exists": true}})
+                        .build(),
+                )
+                .build(),
+            None,
+        )
+        .await?;
+    // Same for the series index, which now rejects a number taken twice.
+    let _ = events
+        .drop_index("plugin_1_event.series.id_1_event.series.number_1", None)
+        .await;
+    events
+        .create_index(
+            IndexModel::builder()
+                .keys(doc! {"plugin": 1, "event.series.id": 1, "event.series.number": 1})
+                .options(
+                    IndexOptions::builder()
+                        .name("series_number_unique".to_string())
+                        .unique(true)
+                        .partial_filter_expression(doc! {"event.series": {"$exists": true}})
+                        .build(),
+                )
+                .build(),
+            None,
+        )
+        .await?;
+    Ok(())
+}
+
+/// Loads the experiences inside `query_range` and merges them with the create placeholders for every full hour in `timezone`, ordered by start time.
+/// Children are listed inside their parent instead of on their own when the parent covers their whole time.
+pub async fn find_compressed_events(
+    events: &Collection<Document>,
+    query_range: &TimeRange,
+    locale: Locale,
+    timezone: Tz,
+) -> APIResult<Vec<CompressedEvent>> {
+    let in_range = find_sorted_projections(
+        events,
+        Database::combine_documents(
+            Database::generate_range_filter(query_range),
+            Database::generate_find_plugin_filter(AvailablePlugins::timeline_plugin_experience),
+        ),
+    )
+    .await?;
+
+    // Children are loaded separately, so a parent lists all of them even when only part of it is inside the range.
+    let parent_ids = in_range.iter().map(|v| v.event.id.clone()).collect::<Vec<_>>();
+    let mut children: HashMap<String, Vec<ExperienceProjection>> = HashMap::new();
+    if !parent_ids.is_empty() {
+        for child in find_sorted_projections(
+            events,
+            Database::combine_documents(
+                Database::generate_find_plugin_filter(AvailablePlugins::timeline_plugin_experience),
+                doc! {"event.parent_id": {"$in": parent_ids}},
+            ),
+        )
+        .await?
+        {
+            if let Some(parent_id) = child.event.parent_id.clone() {
+                children.entry(parent_id).or_default().push(child);
+            }
+        }
+    }
+
+    let series_ids = in_range
+        .iter()
+        .filter_map(|v| v.event.series.as_ref().map(|s| s.id.clone()))
+        .collect::<HashSet<_>>()
+        .into_iter()
+        .collect();
+    let series_members = series::find_members(events, series_ids).await?;
 
-            let mut current = query_range.start;
-
-            while current < query_range.end {
-                let new_current = current
-                    .checked_add_signed(TimeDelta::try_hours(1).unwrap())
-                    .unwrap();
-                let timing = Timing::Instant(current);
-                result.push(CompressedEvent {
-                    title: "Create Experience".to_string(),
-                    time: timing.clone(),
-                    data: serde_json::to_value(CompressedExperienceEvent::Create(timing)).unwrap(),
-                });
-                current = new_current;
+    let parent_timings = in_range
+        .iter()
+        .map(|v| (v.event.id.clone(), v.timing.clone()))
+        .collect::<HashMap<_, _>>();
+    let parents = in_range
+        .iter()
+        .filter_map(|v| v.event.parent_id.as_ref().map(|p| (v.event.id.as_str(), p.as_str())))
+        .collect::<HashMap<_, _>>();
+    // Parents that loop back to the experience would hide every member of the loop, so they are ignored.
+    let in_loop = |id: &str| {
+        let mut seen = HashSet::new();
+        let mut current = id;
+        while let Some(&parent) = parents.get(current) {
+            if parent == id {
+                return true;
             }
+            if !seen.insert(parent) {
+                return false;
+            }
+            current = parent;
+        }
+        false
+    };
+    let collapsed = |v: &ExperienceProjection| {
+        v.event
+            .parent_id
+            .as_ref()
+            .and_then(|parent_id| parent_timings.get(parent_id))
+            .is_some_and(|parent| covers(parent, &v.timing))
+            && !in_loop(&v.event.id)
+    };
+
+    let mut experiences = Vec::new();
+    for t in in_range.iter().filter(|&v| !collapsed(v)) {
+        let own_children = children
+            .remove(&t.event.id)
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|v| covers(&t.timing, &v.timing) && !in_loop(&v.event.id))
+            .map(|v| v.event.to_child(v.timing))
+            .collect();
+        experiences.push(CompressedEvent {
+            title: t.event.name.clone(),
+            time: t.timing.clone(),
+            data: serde_json::to_value(t.event.to_compressed_with(
+                own_children,
+                t.event
+                    .series
+                    .as_ref()
+                    .map(|v| v.badge(series_members.get(&v.id))),
+            ))
+            .unwrap(),
+        })
+    }
 
-            Ok(result)
+    let placeholders = hourly_slots(query_range, timezone)
+        .into_iter()
+        .map(|start| {
+            let timing = Timing::Instant(start);
+            CompressedEvent {
+                title: locale.translate(Message::CreateExperience).to_string(),
+                time: timing.clone(),
+                data: serde_json::to_value(CompressedExperienceEvent::Create(timing)).unwrap(),
+            }
         })
+        .collect();
+
+    Ok(merge_by_time(experiences, placeholders))
+}
+
+async fn find_projections(
+    events: &Collection<Document>,
+    filter: Document,
+    sort: Document,
+) -> APIResult<Vec<ExperienceProjection>> {
+    let options = FindOptions::builder()
+        .projection(doc! {"_id": 0, "timing": 1, "event": 1})
+        .sort(sort)
+        .build();
+    let mut cursor = events
+        .clone_with_type::<ExperienceProjection>()
+        .find(filter, options)
+        .await?;
+    let mut result = Vec::new();
+    while let Some(v) = cursor.next().await {
+        result.push(v?);
     }
+    Ok(result)
 }
 
-#[post("/create", data = "<request>")]
-async fn create_experience(
-    request: Json<CreateExperienceRequest>,
-    cookies: &CookieJar<'_>,
-    config: &State<Config>,
-    database: &State<Arc<Database>>,
-    experience_config: &State<ConfigData>,
-) -> Custom<Json<APIResult<()>>> {
-    if auth(cookies, config).is_err() {
-        return status::Custom(
-            Status::Unauthorized,
-            Json(Err(APIError::AuthenticationError)),
-        );
-    }
-
-    let client = reqwest::Client::new();
-    let id = match client.post(experience_config.url.join("/api/experience/create").unwrap()).header(reqwest::header::COOKIE, format!("pwd={}", experience_config.password)).body(serde_json::to_string(&request.0).unwrap()).send().await {
-        Ok(v) => {
-            match v.text().await {
-                Ok(v) => {
-                    match serde_json::from_str::<APIResult<String>>(&v) {
-                        Ok(v) => match v {
-                            Ok(v) => v,
-                            Err(e) => {
-                                return status::Custom(Status::InternalServerError, Json(Err(APIError::SerdeJsonError(format!("Unable to create experience on experiences server: The experiences server reported an error: {}", e)))))
-                            }
-                        },
-                        Err(e) => {
-                            return status::Custom(Status::InternalServerError, Json(Err(APIError::SerdeJsonError(format!("Unable to read response from experiences server: {}", e)))));
-                        }
-                    }
-                },
+/// Projections matching `filter`, ordered by start time. Instants and ranges are queried separately,
+/// so each query is sorted by the compound index on its timing field.
+async fn find_sorted_projections(
+    events: &Collection<Document>,
+    filter: Document,
+) -> APIResult<Vec<ExperienceProjection>> {
+    let instants = find_projections(
+        events,
+        Database::combine_documents(filter.clone(), doc! {"timing.Instant": {"$exists": true}}),
+        doc! {"plugin": 1, "timing.Instant": 1},
+    )
+    .await?;
+    let ranges = find_projections(
+        events,
+        Database::combine_documents(filter, doc! {"timing.Range": {"$exists": true}}),
+        doc! {"plugin": 1, "timing.Range.start": 1, "timing.Range.end": 1},
+    )
+    .await?;
+    Ok(merge_sorted(instants, ranges, |v| timing_start(&v.timing)))
+}
+
+/// Whether `inner` lies completely within `outer`.
+fn covers(outer: &Timing, inner: &Timing) -> bool {
+    timing_start(outer) <= timing_start(inner) && timing_end(inner) <= timing_end(outer)
+}
+
+fn timing_start(timing: &Timing) -> DateTime<Utc> {
+    match timing {
+        Timing::Instant(v) => *v,
+        Timing::Range(v) => v.start,
+    }
+}
+
+fn timing_end(timing: &Timing) -> DateTime<Utc> {
+    match timing {
+        Timing::Instant(v) => *v,
+        Timing::Range(v) => v.end,
+    }
+}
+
+/// Merges two lists that are already sorted by start time. On equal start times entries of `a` come first.
+fn merge_by_time(a: Vec<CompressedEvent>, b: Vec<CompressedEvent>) -> Vec<CompressedEvent> {
+    merge_sorted(a, b, |v| timing_start(&v.time))
+}
+
+/// Merges two lists that are already sorted by `start`. On equal keys entries of `a` come first.
+fn merge_sorted<T>(a: Vec<T>, b: Vec<T>, start: impl Fn(&T) -> DateTime<Utc>) -> Vec<T> {
+    let mut result = Vec::with_capacity(a.len() + b.len());
+    let mut a = a.into_iter().peekable();
+    let mut b = b.into_iter().peekable();
+    loop {
+        let next = match (a.peek(), b.peek()) {
+            (Some(x), Some(y)) => {
+                if start(y) < start(x) {
+                    b.next()
+                } else {
+                    a.next()
+                }
+            }
+            (Some(_), None) => a.next(),
+            (None, Some(_)) => b.next(),
+            (None, None) => break,
+        };
+        result.extend(next);
+    }
+    result
+}
+
+/// Managed state used by the routes that change experiences.
+pub(crate) struct Services<'r> {
+    pub config: &'r State<Config>,
+    pub database: &'r State<Arc<Database>>,
+    pub experiences: &'r State<ExperiencesClient>,
+    pub experience_config: &'r State<ConfigData>,
+    pub metrics: &'r State<Arc<Metrics>>,
+    pub live_updates: &'r State<LiveUpdates>,
+}
+
+#[server_api::external::rocket::async_trait]
+impl<'r> FromRequest<'r> for Services<'r> {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let rocket = request.rocket();
+        match (
+            State::get(rocket),
+            State::get(rocket),
+            State::get(rocket),
+            State::get(rocket),
+            State::get(rocket),
+            State::get(rocket),
+        ) {
+            (
+                Some(config),
+                Some(database),
+                Some(experiences),
+                Some(experience_config),
+                Some(metrics),
+                Some(live_updates),
+            ) => Outcome::Success(Services {
+                config,
+                database,
+                experiences,
+                experience_config,
+                metrics,
+                live_updates,
+            }),
+            _ => Outcome::Error((Status::InternalServerError, ())),
+        }
+    }
+}
+
+/// Name of the cookie holding the password.
+pub(crate) const AUTH_COOKIE: &str = "pwd";
+
+/// Whether the request carries a cookie that authenticates it. For request guards, which can not use `Services`.
+pub(crate) fn is_authenticated(request: &Request<'_>) -> bool {
+    request
+        .rocket()
+        .state::<Config>()
+        .is_some_and(|config| auth(request.cookies(), config).is_ok())
+}
+
+/// Config accepting `password`, for tests of routes and guards that authenticate.
+#[cfg(test)]
+pub(crate) fn test_config(password: &str) -> Config {
+    toml::from_str(&format!(
+        "password = \"{}\"\nerror_report_url = \"http://errors.example\"",
+        password
+    ))
+    .unwrap()
+}
+
+impl Services<'_> {
+    pub fn authenticate(&self, cookies: &CookieJar<'_>) -> Result<(), (Status, APIError)> {
+        auth(cookies, self.config).map_err(|_| {
+            self.metrics.failure("unauthorized");
+            (Status::Unauthorized, APIError::AuthenticationError)
+        })
+    }
+}
+
+pub(crate) fn respond<T>(result: Result<T, (Status, APIError)>) -> Custom<Json<APIResult<T>>> {
+    match result {
+        Ok(v) => status::Custom(Status::Ok, Json(Ok(v))),
+        Err((status, e)) => status::Custom(status, Json(Err(e))),
+    }
+}
+
+/// Whether a write failed because it violates a unique index.
+pub(crate) fn is_duplicate_key(e: &server_api::external::mongodb::error::Error) -> bool {
+    matches!(
+        e.kind.as_ref(),
+        ErrorKind::Write(WriteFailure::WriteError(v)) if v.code == 11000
+    )
+}
+
+async fn find_by_idempotency_key(
+    database: &Database,
+    key: &str,
+) -> Result<Option<Event<DatabaseExperience>>, server_api::external::mongodb::error::Error> {
+    database
+        .get_events::<DatabaseExperience>()
+        .find_one(
+            Database::combine_documents(
+                Database::generate_find_plugin_filter(AvailablePlugins::timeline_plugin_experience),
+                doc! {"event.idempotency_key": key},
+            ),
+            None,
+        )
+        .instrument(tracing::info_span!("database", operation = "find_idempotency_key"))
+        .await
+}
+
+/// Deepest chain of parents followed when checking a new parent.
+const MAX_NESTING: usize = 64;
+/// Age after which a reservation without an experience counts as abandoned, longer than any create takes.
+const RESERVATION_TIMEOUT_MINS: i64 = 10;
+
+/// Ids of `id` and its parents, nearest first. Rejects unknown experiences and chains that loop or nest deeper than
+/// `MAX_NESTING`.
+pub(crate) async fn ancestors(database: &Database, id: &str) -> Result<Vec<String>, (Status, APIError)> {
+    let events = database.get_events::<DatabaseExperience>();
+    let mut chain = Vec::new();
+    let mut current = Some(id.to_string());
+    while let Some(next) = current {
+        if chain.contains(&next) || chain.len() >= MAX_NESTING {
+            return Err((
+                Status::BadRequest,
+                APIError::Custom(format!("The parents of {} form a loop", id)),
+            ));
+        }
+        let parent = events
+            .find_one(experience_filter(&next), None)
+            .instrument(tracing::info_span!("database", operation = "find_parent"))
+            .await
+            .map_err(|e| (Status::InternalServerError, e.into()))?
+            .ok_or_else(|| {
+                (
+                    Status::BadRequest,
+                    APIError::Custom(format!("Unknown parent experience: {}", next)),
+                )
+            })?;
+        current = parent.event.parent_id;
+        chain.push(next);
+    }
+    Ok(chain)
+}
+
+/// Experience returned by `create_once`. Serialized as its id.
+#[derive(Serialize)]
+#[serde(untagged)]
+pub(crate) enum Created {
+    New(String),
+    /// Created by an earlier request with the same idempotency key.
+    Existing(String),
+}
+
+impl Created {
+    pub fn into_id(self) -> String {
+        match self {
+            Created::New(id) | Created::Existing(id) => id,
+        }
+    }
+}
+
+/// Claims an idempotency key before the experience is created upstream, so concurrent replays can not create it twice.
+/// Returns the experience an earlier request with the key created, `None` if the key is new. A reservation left
+/// without an experience for `RESERVATION_TIMEOUT_MINS`, by a process that died during the create, is taken over.
+async fn reserve_key(database: &Database, key: &str) -> Result<Option<String>, (Status, APIError)> {
+    let reservations = plugin_collection::<Document>(database, "idempotency_keys");
+    let now = bson::DateTime::now();
+    match reservations
+        .insert_one(doc! {"_id": key, "experience_id": null, "reserved_at": now}, None)
+        .await
+    {
+        Ok(_) => return Ok(None),
+        Err(e) if is_duplicate_key(&e) => {}
+        Err(e) => return Err((Status::InternalServerError, e.into())),
+    }
+    let expired = bson::DateTime::from_millis(now.timestamp_millis() - RESERVATION_TIMEOUT_MINS * 60_000);
+    let taken_over = reservations
+        .update_one(
+            doc! {
+                "_id": key,
+                "experience_id": null,
+                "$or": [{"reserved_at": {"$lt": expired}}, {"reserved_at": {"$exists": false}}],
+            },
+            doc! {"$set": {"reserved_at": now}},
+            None,
+        )
+        .await
+        .map_err(|e| (Status::InternalServerError, e.into()))?;
+    if taken_over.modified_count == 1 {
+        tracing::warn!(idempotency_key = key, "took over an abandoned idempotency key");
+        return Ok(None);
+    }
+    let reservation = reservations
+        .find_one(doc! {"_id": key}, None)
+        .await
+        .map_err(|e| (Status::InternalServerError, e.into()))?;
+    match reservation.and_then(|v| v.get_str("experience_id").ok().map(|v| v.to_string())) {
+        Some(id) => Ok(Some(id)),
+        None => Err((
+            Status::Conflict,
+            APIError::Custom(format!(
+                "A request with the idempotency key {} is still in progress",
+                key
+            )),
+        )),
+    }
+}
+
+/// Creates an experience on the experiences server and registers it on the timeline. Tells whether the idempotency key
+/// of the request returned an experience created earlier instead.
+#[tracing::instrument(name = "create_experience", skip_all, fields(request_id = %request_id, experience_id = tracing::field::Empty))]
+pub(crate) async fn create_once(
+    services: &Services<'_>,
+    request: &CreateExperienceRequest,
+    series: Option<Series>,
+    request_id: &RequestId,
+) -> Result<Created, (Status, APIError)> {
+    let Services {
+        config,
+        database,
+        experiences,
+        experience_config,
+        metrics,
+        live_updates,
+    } = services;
+
+    if timing_end(&request.time) < timing_start(&request.time) {
+        return Err((
+            Status::BadRequest,
+            APIError::Custom("Invalid timing: the end is before the start".to_string()),
+        ));
+    }
+    if let Some(color) = request.color.as_deref().filter(|v| !color::is_valid_color(v)) {
+        return Err((
+            Status::BadRequest,
+            APIError::Custom(format!("Invalid color: {}", color)),
+        ));
+    }
+    if let Some(icon) = request.icon.as_deref().filter(|v| !color::is_valid_icon(v)) {
+        return Err((
+            Status::BadRequest,
+            APIError::Custom(format!("Invalid icon: {}", icon)),
+        ));
+    }
+
+    if let Some(parent_id) = &request.parent_id {
+        ancestors(database, parent_id).await.inspect_err(|(status, _)| {
+            if *status == Status::InternalServerError {
+                metrics.failure("database");
+            }
+        })?;
+    }
+
+    let reserved = match &request.idempotency_key {
+        Some(key) => {
+            match find_by_idempotency_key(database, key).await {
+                Ok(Some(existing)) => return Ok(Created::Existing(existing.id)),
+                Ok(None) => {}
                 Err(e) => {
-                    return status::Custom(Status::InternalServerError, Json(Err(APIError::Custom(format!("Unable to read response from experiences server: {}", e)))));
+                    metrics.failure("database");
+                    return Err((Status::InternalServerError, e.into()));
                 }
             }
+            reserve_key(database, key).await?
         }
-        Err(e) => {
-            return status::Custom(Status::InternalServerError, Json(Err(APIError::Custom(format!("Unable to send request to experiences server: {}", e)))));
+        None => None,
+    };
+
+    let id = match reserved {
+        // Created upstream by an earlier request whose registration on the timeline failed, so it is only registered now.
+        Some(id) => id,
+        None => {
+            let body = UpstreamCreateExperience {
+                name: &request.name,
+                time: &request.time,
+                tags: &request.tags,
+            };
+            let reservations = plugin_collection::<Document>(database, "idempotency_keys");
+            let id = match experiences
+                .post::<_, String>("create", "/api/experience/create", &body, request_id)
+                .await
+            {
+                Ok(id) => id,
+                Err(e) => {
+                    metrics.failure(e.kind());
+                    // Nothing was created, so a retry with the same key may try again.
+                    if let Some(key) = &request.idempotency_key {
+                        let _ = reservations.delete_one(doc! {"_id": key}, None).await;
+                    }
+                    return Err((
+                        Status::InternalServerError,
+                        e.to_api_error(experience_config.locale),
+                    ));
+                }
+            };
+            // Stored before registering, so the key still leads to the experience if the registration fails.
+            if let Some(key) = &request.idempotency_key {
+                if let Err(e) = reservations
+                    .update_one(doc! {"_id": key}, doc! {"$set": {"experience_id": &id}}, None)
+                    .await
+                {
+                    tracing::error!(experience_id = %id, "unable to store idempotency key: {}", e);
+                }
+            }
+            id
         }
     };
+    tracing::Span::current().record("experience_id", id.as_str());
 
+    let experience = DatabaseExperience {
+        name: request.name.clone(),
+        id: id.clone(),
+        idempotency_key: request.idempotency_key.clone(),
+        color: request.color.clone(),
+        icon: request.icon.clone(),
+        cover_color: None,
+        tags: request.tags.clone(),
+        parent_id: request.parent_id.clone(),
+        series: series.or_else(|| request.series_name.clone().map(Series::start)),
+        sources: request.sources.clone(),
+    };
     match database
         .register_single_event(&Event {
             timing: request.time.clone(),
             id: id.clone(),
             plugin: <Plugin as PluginTrait>::get_type(),
-            event: DatabaseExperience {
-                name: request.name.clone(),
-                id,
-            },
+            event: &experience,
         })
+        .instrument(tracing::info_span!("database", operation = "register_event", experience_id = %id))
         .await
     {
-        Ok(_) => status::Custom(Status::Ok, Json(Ok(()))),
+        Ok(_) => {
+            metrics.creates.inc();
+            live_updates.send(LiveUpdate::Changed {
+                title: request.name.clone(),
+                time: request.time.clone(),
+                data: experience.to_compressed(),
+            });
+            Ok(Created::New(id))
+        }
+        // A concurrent request with the same key registered the experience first.
+        Err(e) if is_duplicate_key(&e) => match request.idempotency_key.as_ref() {
+            Some(key) => match find_by_idempotency_key(database, key).await {
+                Ok(Some(existing)) => Ok(Created::Existing(existing.id)),
+                Ok(None) => Err((Status::InternalServerError, e.into())),
+                Err(e) => Err((Status::InternalServerError, e.into())),
+            },
+            None => Err((Status::InternalServerError, e.into())),
+        },
         Err(e) => {
+            metrics.failure("database");
+            tracing::error!(experience_id = %id, "unable to register experience event");
             server_api::error::error(
                 database.inner().clone(),
                 &e,
                 Some(<Plugin as PluginTrait>::get_type()),
                 &config.error_report_url,
             );
-            status::Custom(Status::InternalServerError, Json(Err(e.into())))
+            Err((Status::InternalServerError, e.into()))
         }
     }
 }
+
+#[post("/create", data = "<request>")]
+async fn create_experience(
+    _same_origin: SameOrigin,
+    _rate_limit: RateLimited,
+    request: Json<CreateExperienceRequest>,
+    cookies: &CookieJar<'_>,
+    services: Services<'_>,
+    request_id: RequestId,
+    actor: Actor,
+) -> Custom<Json<APIResult<String>>> {
+    let result = async {
+        services.authenticate(cookies)?;
+        create_once(&services, &request, None, &request_id).await
+    }.await;
+    audit::record_created(&services, &actor, "create", &request_id, None, &result).await;
+    respond(result.map(Created::into_id))
+}
@@ -0,0 +1,81 @@
+use {
+    crate::CompressedExperienceEvent,
+    serde::Serialize,
+    server_api::{
+        config::Config,
+        external::{
+            rocket::{
+                get,
+                http::{CookieJar, Status},
+                response::stream::{Event, EventStream},
+                tokio::{
+                    select,
+                    sync::broadcast::{self, error::RecvError},
+                },
+                Shutdown, State,
+            },
+            types::timing::Timing,
+        },
+        web::auth,
+    },
+};
+
+/// Change of an experience, pushed to subscribed clients. `Changed` has the same shape as a `CompressedEvent` of this plugin.
+#[derive(Serialize, Clone)]
+pub enum LiveUpdate {
+    Changed {
+        title: String,
+        time: Timing,
+        data: CompressedExperienceEvent,
+    },
+    Removed {
+        id: String,
+    },
+}
+
+pub struct LiveUpdates(broadcast::Sender<LiveUpdate>);
+
+impl LiveUpdates {
+    pub fn new() -> Self {
+        LiveUpdates(broadcast::channel(64).0)
+    }
+
+    pub fn send(&self, update: LiveUpdate) {
+        // Failing only means that nobody is subscribed right now.
+        let _ = self.0.send(update);
+    }
+}
+
+impl Default for LiveUpdates {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Server-sent events stream of `LiveUpdate`s.
+#[get("/live")]
+pub fn live(
+    cookies: &CookieJar<'_>,
+    config: &State<Config>,
+    updates: &State<LiveUpdates>,
+    mut shutdown: Shutdown,
+) -> Result<EventStream![], Status> {
+    if auth(cookies, config).is_err() {
+        return Err(Status::Unauthorized);
+    }
+
+    let mut receiver = updates.0.subscribe();
+    Ok(EventStream! {
+        loop {
+            let update = select! {
+                update = receiver.recv() => match update {
+                    Ok(v) => v,
+                    Err(RecvError::Closed) => break,
+                    Err(RecvError::Lagged(_)) => continue,
+                },
+                _ = &mut shutdown => break,
+            };
+            yield Event::json(&update);
+        }
+    })
+}
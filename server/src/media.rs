@@ -0,0 +1,90 @@
+use {
+    crate::{
+        audit::{self, Actor, AuditEntry},
+        color,
+        csrf::SameOrigin,
+        experiences::RequestId,
+        merge::find_experience,
+        rate_limit::RateLimited,
+        respond,
+        upload::{sanitize_filename, spool, upload_media},
+        Services,
+    },
+    server_api::external::{
+        mongodb::bson::doc,
+        rocket::{
+            data::Data,
+            get,
+            http::{CookieJar, Status},
+            post,
+            response::status::Custom,
+            serde::json::Json,
+        },
+        types::api::{APIError, APIResult},
+    },
+};
+
+/// Ids of the media of an experience, for the thumbnail strip of its card.
+#[get("/experience/<id>/media")]
+pub async fn list_media(
+    id: &str,
+    cookies: &CookieJar<'_>,
+    services: Services<'_>,
+    request_id: RequestId,
+) -> Custom<Json<APIResult<Vec<String>>>> {
+    respond(
+        async {
+            services.authenticate(cookies)?;
+            find_experience(&services, id).await?;
+            services
+                .experiences
+                .get::<Vec<String>>("list_media", &format!("/api/experience/{}/media", id), &request_id)
+                .await
+                .map_err(|e| {
+                    services.metrics.failure(e.kind());
+                    (
+                        Status::BadGateway,
+                        e.to_api_error(services.experience_config.locale),
+                    )
+                })
+        }
+        .await,
+    )
+}
+
+/// Adds a photo or video to an existing experience. Live clients are told to reload its cover.
+#[post("/experience/<id>/media?<filename>", data = "<data>")]
+#[allow(clippy::too_many_arguments)]
+pub async fn add_media(
+    _same_origin: SameOrigin,
+    _rate_limit: RateLimited,
+    id: &str,
+    filename: &str,
+    data: Data<'_>,
+    cookies: &CookieJar<'_>,
+    services: Services<'_>,
+    request_id: RequestId,
+    actor: Actor,
+) -> Custom<Json<APIResult<()>>> {
+    let result = async {
+        services.authenticate(cookies)?;
+        let filename = sanitize_filename(filename).ok_or_else(|| {
+            (
+                Status::BadRequest,
+                APIError::Custom("Missing file name".to_string()),
+            )
+        })?;
+        find_experience(&services, id).await?;
+        let upload = spool(&services, data).await?;
+        upload_media(&services, id, &filename, &upload.0, &request_id).await?;
+        color::refresh(&services, id, &request_id).await;
+        Ok(())
+    }
+    .await;
+    let entry = AuditEntry::new(&actor, "add_media", &request_id)
+        .target(Some(id))
+        .after(Some(doc! {"filename": filename}))
+        .result(&result);
+    audit::record(services.database, entry).await;
+    respond(result)
+}
@@ -0,0 +1,109 @@
+use {
+    chrono_tz::Tz,
+    exif::{In, Reader, Tag, Value},
+    server_api::external::types::external::chrono::{
+        DateTime, NaiveDateTime, TimeDelta, TimeZone, Utc,
+    },
+    std::{
+        fs::File,
+        io::{BufReader, Read, Seek, SeekFrom},
+        path::Path,
+    },
+};
+
+/// When a photo or video was taken, from the EXIF data of a photo or the `mvhd` box of an MP4 or QuickTime video.
+/// EXIF dates without an offset are read as wall-clock time in `timezone`.
+pub fn creation_date(path: &Path, timezone: Tz) -> Option<DateTime<Utc>> {
+    exif_date(path, timezone).or_else(|| movie_date(path))
+}
+
+fn ascii_field(exif: &exif::Exif, tag: Tag) -> Option<String> {
+    match &exif.get_field(tag, In::PRIMARY)?.value {
+        Value::Ascii(values) => Some(String::from_utf8_lossy(values.first()?).trim().to_string()),
+        _ => None,
+    }
+}
+
+fn exif_date(path: &Path, timezone: Tz) -> Option<DateTime<Utc>> {
+    let file = File::open(path).ok()?;
+    let exif = Reader::new()
+        .read_from_container(&mut BufReader::new(file))
+        .ok()?;
+    let (date, offset) = [
+        (Tag::DateTimeOriginal, Tag::OffsetTimeOriginal),
+        (Tag::DateTimeDigitized, Tag::OffsetTimeDigitized),
+        (Tag::DateTime, Tag::OffsetTime),
+    ]
+    .into_iter()
+    .find_map(|(date, offset)| Some((ascii_field(&exif, date)?, ascii_field(&exif, offset))))?;
+
+    if let Some(offset) = offset {
+        if let Ok(v) = DateTime::parse_from_str(&format!("{} {}", date, offset), "%Y:%m:%d %H:%M:%S %:z") {
+            return Some(v.with_timezone(&Utc));
+        }
+    }
+    let local = NaiveDateTime::parse_from_str(&date, "%Y:%m:%d %H:%M:%S").ok()?;
+    timezone
+        .from_local_datetime(&local)
+        .earliest()
+        .map(|v| v.with_timezone(&Utc))
+}
+
+/// Content range of the first box of `kind` between `offset` and `end`. `None` for a box reaching past `end`.
+fn find_box(file: &mut File, mut offset: u64, end: u64, kind: &[u8; 4]) -> Option<(u64, u64)> {
+    while offset.checked_add(8).is_some_and(|v| v <= end) {
+        file.seek(SeekFrom::Start(offset)).ok()?;
+        let mut header = [0u8; 8];
+        file.read_exact(&mut header).ok()?;
+        let mut header_len = 8;
+        let size = match u32::from_be_bytes(header[0..4].try_into().unwrap()) {
+            // The box extends to the end of its parent.
+            0 => end - offset,
+            // The real size follows the type as a 64 bit integer.
+            1 => {
+                let mut size = [0u8; 8];
+                file.read_exact(&mut size).ok()?;
+                header_len = 16;
+                u64::from_be_bytes(size)
+            }
+            v => v as u64,
+        };
+        if size < header_len {
+            return None;
+        }
+        let box_end = offset.checked_add(size).filter(|v| *v <= end)?;
+        if &header[4..8] == kind {
+            return Some((offset + header_len, box_end));
+        }
+        offset = box_end;
+    }
+    None
+}
+
+fn movie_date(path: &Path) -> Option<DateTime<Utc>> {
+    let mut file = File::open(path).ok()?;
+    let len = file.metadata().ok()?.len();
+    let (moov_start, moov_end) = find_box(&mut file, 0, len, b"moov")?;
+    let (mvhd_start, _) = find_box(&mut file, moov_start, moov_end, b"mvhd")?;
+    file.seek(SeekFrom::Start(mvhd_start)).ok()?;
+    // Version and flags, the version decides the width of the timestamps.
+    let mut version = [0u8; 4];
+    file.read_exact(&mut version).ok()?;
+    let seconds = if version[0] == 1 {
+        let mut v = [0u8; 8];
+        file.read_exact(&mut v).ok()?;
+        u64::from_be_bytes(v)
+    } else {
+        let mut v = [0u8; 4];
+        file.read_exact(&mut v).ok()?;
+        u32::from_be_bytes(v) as u64
+    };
+    // Many encoders leave the creation time unset.
+    if seconds == 0 {
+        return None;
+    }
+    // Seconds since 1904-01-01 UTC.
+    Utc.with_ymd_and_hms(1904, 1, 1, 0, 0, 0)
+        .single()?
+        .checked_add_signed(TimeDelta::try_seconds(i64::try_from(seconds).ok()?)?)
+}
@@ -0,0 +1,127 @@
+use {
+    crate::{timing_start, CompressedExperienceEvent, ExperienceProjection},
+    chrono_tz::Tz,
+    serde::Deserialize,
+    server_api::{
+        db::Database,
+        external::{
+            futures::TryStreamExt,
+            mongodb::{
+                bson::{doc, Document},
+                options::FindOptions,
+                Collection,
+            },
+            types::{
+                api::{APIResult, CompressedEvent},
+                available_plugins::AvailablePlugins,
+                external::{
+                    chrono::{DateTime, Months, NaiveDate, TimeZone, Utc},
+                    serde_json,
+                },
+                timing::{TimeRange, Timing},
+            },
+        },
+    },
+    std::collections::HashMap,
+};
+
+/// Settings of the "on this day" memories. They are only shown when this section exists in the config.
+#[derive(Deserialize, Clone)]
+pub struct MemoryConfig {
+    /// How many years back experiences are resurfaced.
+    #[serde(default = "default_max_years")]
+    pub max_years: u32,
+    /// Memories shown per calendar day at most, the oldest years win.
+    #[serde(default = "default_max_per_day")]
+    pub max_per_day: usize,
+}
+
+fn default_max_years() -> u32 {
+    5
+}
+
+fn default_max_per_day() -> usize {
+    3
+}
+
+/// Moves `time` by whole years on the wall clock of `timezone`. The 29th of February becomes the 28th in other years.
+fn shift_years(time: DateTime<Utc>, years: i32, timezone: Tz) -> Option<DateTime<Utc>> {
+    let local = time.with_timezone(&timezone).naive_local();
+    let months = Months::new(years.unsigned_abs() * 12);
+    let shifted = if years >= 0 {
+        local.checked_add_months(months)?
+    } else {
+        local.checked_sub_months(months)?
+    };
+    timezone
+        .from_local_datetime(&shifted)
+        .earliest()
+        .map(|v| v.with_timezone(&Utc))
+}
+
+/// Experiences from the same calendar days as `query_range` in previous years, placed at their anniversary.
+pub async fn find_memories(
+    events: &Collection<Document>,
+    query_range: &TimeRange,
+    config: &MemoryConfig,
+    timezone: Tz,
+) -> APIResult<Vec<CompressedEvent>> {
+    let mut per_day: HashMap<NaiveDate, usize> = HashMap::new();
+    let mut memories = Vec::new();
+    // Oldest years first, so they win when a day has more memories than allowed.
+    for years in (1..=config.max_years).rev() {
+        let (Some(start), Some(end)) = (
+            shift_years(query_range.start, -(years as i32), timezone),
+            shift_years(query_range.end, -(years as i32), timezone),
+        ) else {
+            continue;
+        };
+        let range = TimeRange { start, end };
+        let mut past: Vec<ExperienceProjection> = events
+            .clone_with_type::<ExperienceProjection>()
+            .find(
+                Database::combine_documents(
+                    Database::generate_range_filter(&range),
+                    Database::generate_find_plugin_filter(AvailablePlugins::timeline_plugin_experience),
+                ),
+                FindOptions::builder()
+                    .projection(doc! {"_id": 0, "timing": 1, "event": 1})
+                    .build(),
+            )
+            .await?
+            .try_collect()
+            .await?;
+        past.sort_by_key(|v| timing_start(&v.timing));
+
+        for experience in past {
+            let Some(anniversary) = shift_years(timing_start(&experience.timing), years as i32, timezone)
+            else {
+                continue;
+            };
+            if anniversary < query_range.start || anniversary >= query_range.end {
+                continue;
+            }
+            let shown = per_day
+                .entry(anniversary.with_timezone(&timezone).date_naive())
+                .or_default();
+            if *shown >= config.max_per_day {
+                continue;
+            }
+            *shown += 1;
+            memories.push(CompressedEvent {
+                title: experience.event.name.clone(),
+                time: Timing::Instant(anniversary),
+                data: serde_json::to_value(CompressedExperienceEvent::Memory {
+                    id: experience.event.id.clone(),
+                    name: experience.event.name.clone(),
+                    years,
+                    color: experience.event.color(),
+                    icon: experience.event.icon.clone(),
+                })
+                .unwrap(),
+            });
+        }
+    }
+    memories.sort_by_key(|v| timing_start(&v.time));
+    Ok(memories)
+}
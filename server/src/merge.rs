@@ -0,0 +1,388 @@
+use {
+    crate::{
+        ancestors,
+        audit::{self, Actor, AuditEntry},
+        csrf::SameOrigin,
+        experience_filter,
+        experiences::RequestId,
+        is_duplicate_key,
+        live::LiveUpdate,
+        plugin_collection,
+        rate_limit::RateLimited,
+        respond, timing_end, timing_start, DatabaseExperience, Services,
+    },
+    serde::{Deserialize, Serialize},
+    server_api::{
+        db::{Database, Event},
+        external::{
+            mongodb::{
+                bson::{self, doc},
+                options::IndexOptions,
+                IndexModel,
+            },
+            rocket::{
+                http::{CookieJar, Status},
+                post,
+                response::status::Custom,
+                serde::json::Json,
+            },
+            types::{
+                api::{APIError, APIResult},
+                available_plugins::AvailablePlugins,
+                timing::{TimeRange, Timing},
+            },
+        },
+    },
+    tracing::Instrument,
+};
+
+#[derive(Deserialize)]
+pub struct MergeRequest {
+    /// Experience that is merged and deleted afterwards.
+    pub source: String,
+    /// Experience that receives the media and keeps existing.
+    pub target: String,
+}
+
+#[derive(Serialize)]
+struct MoveMediaRequest<'a> {
+    target: &'a str,
+}
+
+#[derive(Serialize)]
+struct SetTimeRequest<'a> {
+    time: &'a Timing,
+}
+
+/// Smallest range containing both timings.
+fn covering(a: &Timing, b: &Timing) -> Timing {
+    Timing::Range(TimeRange {
+        start: timing_start(a).min(timing_start(b)),
+        end: timing_end(a).max(timing_end(b)),
+    })
+}
+
+pub(crate) async fn find_experience(
+    services: &Services<'_>,
+    id: &str,
+) -> Result<Event<DatabaseExperience>, (Status, APIError)> {
+    services
+        .database
+        .get_events::<DatabaseExperience>()
+        .find_one(experience_filter(id), None)
+        .await
+        .map_err(|e| (Status::InternalServerError, e.into()))?
+        .ok_or_else(|| {
+            (
+                Status::NotFound,
+                APIError::Custom(format!("Unknown experience: {}", id)),
+            )
+        })
+}
+
+/// Upstream step of a merge. Each one is recorded once it succeeded, so a retried merge continues after it.
+#[derive(Serialize, Deserialize, PartialEq, Clone, Copy)]
+enum MergeStep {
+    /// `POST /api/experience/{target}/time`. Setting the same time again changes nothing.
+    SetTime,
+    /// `POST /api/experience/{source}/media/move`. Moves what is left, nothing once everything was moved.
+    MoveMedia,
+    /// `POST /api/experience/{source}/delete`. Fails once the source is gone, so it runs last and only once.
+    Delete,
+}
+
+const PENDING_MERGES: &str = "pending_merges";
+
+/// Merge that was started but not finished, stored under the id of its source. The time is fixed when the merge starts,
+/// a retry can not compute it again once the source is deleted.
+#[derive(Serialize, Deserialize)]
+struct PendingMerge {
+    #[serde(rename = "_id")]
+    source: String,
+    target: String,
+    /// Source and target. Unique across all pending merges, so an experience takes part in one merge at a time.
+    #[serde(default)]
+    ids: Vec<String>,
+    time: Timing,
+    /// Parent of the source, taken over by the target when the target was one of the source's children.
+    #[serde(default)]
+    parent_id: Option<String>,
+    #[serde(default)]
+    done: Vec<MergeStep>,
+}
+
+/// Unique index on the experiences of pending merges, so claiming both of them is a single insert.
+pub(crate) async fn ensure_indexes(
+    database: &Database,
+) -> Result<(), server_api::external::mongodb::error::Error> {
+    plugin_collection::<PendingMerge>(database, PENDING_MERGES)
+        .create_index(
+            IndexModel::builder()
+                .keys(doc! {"ids": 1})
+                .options(
+                    IndexOptions::builder()
+                        .name("pending_merge_ids_unique".to_string())
+                        .unique(true)
+                        // Records of older versions have no ids and must not collide with each other.
+                        .partial_filter_expression(doc! {"ids": {"$exists": true}})
+                        .build(),
+                )
+                .build(),
+            None,
+        )
+        .await?;
+    Ok(())
+}
+
+/// Rejects a target below the source deeper than a direct child. A direct child takes the place of the source, but a
+/// deeper one would end up under its own ancestor once the source's children move under it. `target_ancestors` starts
+/// with the target's parent.
+fn check_descent(source: &str, target_ancestors: &[String]) -> Result<(), (Status, APIError)> {
+    match target_ancestors.iter().position(|v| v == source) {
+        Some(depth) if depth > 0 => Err((
+            Status::BadRequest,
+            APIError::Custom(format!(
+                "An experience can not be merged into one nested deeper inside it: {}",
+                source
+            )),
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// Loads the pending merge of `request.source`, or records a new one. A merge interrupted by an upstream failure is
+/// resumed by sending the same request again. Until then, neither experience can take part in another merge.
+async fn start_merge(
+    services: &Services<'_>,
+    request: &MergeRequest,
+) -> Result<PendingMerge, (Status, APIError)> {
+    let pending = plugin_collection::<PendingMerge>(services.database, PENDING_MERGES);
+    if let Some(merge) = pending
+        .find_one(doc! {"_id": &request.source, "target": &request.target}, None)
+        .await
+        .map_err(|e| (Status::InternalServerError, e.into()))?
+    {
+        return Ok(merge);
+    }
+
+    let source = find_experience(services, &request.source).await?;
+    let target = find_experience(services, &request.target).await?;
+    if let Some(parent_id) = &target.event.parent_id {
+        check_descent(&request.source, &ancestors(services.database, parent_id).await?)?;
+    }
+    let merge = PendingMerge {
+        source: request.source.clone(),
+        target: request.target.clone(),
+        ids: vec![request.source.clone(), request.target.clone()],
+        time: covering(&source.timing, &target.timing),
+        parent_id: source.event.parent_id.clone(),
+        done: Vec::new(),
+    };
+    // The unique index on `ids` makes this fail for any merge sharing an experience, including one in the opposite
+    // direction started at the same time.
+    match pending.insert_one(&merge, None).await {
+        Ok(_) => Ok(merge),
+        Err(e) if is_duplicate_key(&e) => Err((
+            Status::Conflict,
+            APIError::Custom(format!(
+                "The experience {} or {} is already being merged",
+                request.source, request.target
+            )),
+        )),
+        Err(e) => Err((Status::InternalServerError, e.into())),
+    }
+}
+
+/// Moves the media of `source` into `target`, widens the timing of `target` to cover both and deletes `source`.
+/// The upstream routes are called in the order of `MergeStep`, and the progress is stored in between. If one of them
+/// fails, the merge stays pending and the same request picks it up where it stopped.
+#[tracing::instrument(name = "merge_experiences", skip_all, fields(request_id = %request_id, source = %request.source, target = %request.target))]
+async fn merge(
+    services: &Services<'_>,
+    request: &MergeRequest,
+    request_id: &RequestId,
+) -> Result<(), (Status, APIError)> {
+    if request.source == request.target {
+        return Err((
+            Status::BadRequest,
+            APIError::Custom("An experience can not be merged into itself".to_string()),
+        ));
+    }
+    let mut merge = start_merge(services, request).await?;
+    let time = merge.time.clone();
+
+    let locale = services.experience_config.locale;
+    let upstream_error = |e: crate::experiences::UpstreamError| {
+        services.metrics.failure(e.kind());
+        (Status::BadGateway, e.to_api_error(locale))
+    };
+    let pending = plugin_collection::<PendingMerge>(services.database, PENDING_MERGES);
+    for step in [MergeStep::SetTime, MergeStep::MoveMedia, MergeStep::Delete] {
+        if merge.done.contains(&step) {
+            continue;
+        }
+        match step {
+            MergeStep::SetTime => services
+                .experiences
+                .post::<_, ()>(
+                    "set_time",
+                    &format!("/api/experience/{}/time", request.target),
+                    &SetTimeRequest { time: &time },
+                    request_id,
+                )
+                .await,
+            MergeStep::MoveMedia => services
+                .experiences
+                .post::<_, ()>(
+                    "move_media",
+                    &format!("/api/experience/{}/media/move", request.source),
+                    &MoveMediaRequest {
+                        target: &request.target,
+                    },
+                    request_id,
+                )
+                .await,
+            MergeStep::Delete => services
+                .experiences
+                .post::<_, ()>(
+                    "delete",
+                    &format!("/api/experience/{}/delete", request.source),
+                    &(),
+                    request_id,
+                )
+                .await,
+        }
+        .map_err(upstream_error)?;
+        merge.done.push(step);
+        let done = bson::to_bson(&merge.done)
+            .map_err(|e| (Status::InternalServerError, APIError::Custom(e.to_string())))?;
+        pending
+            .update_one(doc! {"_id": &request.source}, doc! {"$set": {"done": done}}, None)
+            .await
+            .map_err(|e| (Status::InternalServerError, e.into()))?;
+    }
+
+    let events = services.database.get_events::<DatabaseExperience>();
+    let timing = bson::to_bson(&time)
+        .map_err(|e| (Status::InternalServerError, APIError::Custom(e.to_string())))?;
+    // Every write is a no-op when repeated, so a failure here is also fixed by sending the request again.
+    async {
+        events
+            .update_one(
+                experience_filter(&request.target),
+                doc! {"$set": {"timing": timing}},
+                None,
+            )
+            .await?;
+        events
+            .delete_one(experience_filter(&request.source), None)
+            .await?;
+        // A target that was part of the source moves up to the source's parent instead of becoming its own parent.
+        let parent_id = merge.parent_id.as_deref().filter(|v| *v != request.target);
+        events
+            .update_one(
+                Database::combine_documents(
+                    experience_filter(&request.target),
+                    doc! {"event.parent_id": &request.source},
+                ),
+                match parent_id {
+                    Some(parent_id) => doc! {"$set": {"event.parent_id": parent_id}},
+                    None => doc! {"$unset": {"event.parent_id": ""}},
+                },
+                None,
+            )
+            .await?;
+        // The other children of the removed experience become part of the one it was merged into.
+        events
+            .update_many(
+                Database::combine_documents(
+                    Database::generate_find_plugin_filter(AvailablePlugins::timeline_plugin_experience),
+                    doc! {"event.parent_id": &request.source, "id": {"$ne": &request.target}},
+                ),
+                doc! {"$set": {"event.parent_id": &request.target}},
+                None,
+            )
+            .await?;
+        pending.delete_one(doc! {"_id": &request.source}, None).await?;
+        Ok::<_, APIError>(())
+    }
+    .instrument(tracing::info_span!("database", operation = "merge"))
+    .await
+    .map_err(|e| {
+        services.metrics.failure("database");
+        (Status::InternalServerError, e)
+    })?;
+
+    let target = find_experience(services, &request.target).await?;
+    services.live_updates.send(LiveUpdate::Changed {
+        title: target.event.name.clone(),
+        time: target.timing,
+        data: target.event.to_compressed(),
+    });
+    services.live_updates.send(LiveUpdate::Removed {
+        id: request.source.clone(),
+    });
+    Ok(())
+}
+
+#[post("/merge", data = "<request>")]
+pub async fn merge_experiences(
+    _same_origin: SameOrigin,
+    _rate_limit: RateLimited,
+    request: Json<MergeRequest>,
+    cookies: &CookieJar<'_>,
+    services: Services<'_>,
+    request_id: RequestId,
+    actor: Actor,
+) -> Custom<Json<APIResult<()>>> {
+    let authenticated = services.authenticate(cookies);
+    // Unauthenticated attempts are recorded without any state.
+    let before = match authenticated {
+        Ok(()) => Some(doc! {
+            "source": audit::snapshot(services.database, &request.source).await,
+            "target": audit::snapshot(services.database, &request.target).await,
+        }),
+        Err(_) => None,
+    };
+    let result = match authenticated {
+        Ok(()) => merge(&services, &request, &request_id).await,
+        Err(e) => Err(e),
+    };
+    let after = match result {
+        Ok(()) => audit::snapshot(services.database, &request.target).await,
+        Err(_) => None,
+    };
+    let entry = AuditEntry::new(&actor, "merge", &request_id)
+        .target(Some(&request.target))
+        .before(before)
+        .after(after)
+        .result(&result);
+    audit::record(services.database, entry).await;
+    respond(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chain(ids: &[&str]) -> Vec<String> {
+        ids.iter().map(|v| v.to_string()).collect()
+    }
+
+    #[test]
+    fn direct_child_can_be_merged_into() {
+        assert!(check_descent("source", &chain(&["source", "root"])).is_ok());
+    }
+
+    #[test]
+    fn grandchild_is_rejected() {
+        let (status, _) = check_descent("source", &chain(&["child", "source", "root"])).unwrap_err();
+        assert_eq!(status, Status::BadRequest);
+    }
+
+    #[test]
+    fn unrelated_target_can_be_merged_into() {
+        assert!(check_descent("source", &chain(&["other", "root"])).is_ok());
+        assert!(check_descent("source", &[]).is_ok());
+    }
+}
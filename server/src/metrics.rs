@@ -0,0 +1,125 @@
+use {
+    prometheus::{
+        Encoder, HistogramOpts, HistogramVec, IntCounter, IntCounterVec, Opts, Registry,
+        TextEncoder,
+    },
+    crate::Services,
+    server_api::external::rocket::{
+        get,
+        http::{ContentType, CookieJar, Status},
+        request::{FromRequest, Outcome},
+        response::status::Custom,
+        Request,
+    },
+    std::{convert::Infallible, sync::Arc},
+};
+
+pub struct Metrics {
+    registry: Registry,
+    pub creates: IntCounter,
+    pub failures: IntCounterVec,
+    pub upstream_latency: HistogramVec,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        let registry = Registry::new_custom(Some("timeline_plugin_experience".to_string()), None)
+            .expect("Unable to create metrics registry");
+        let creates = IntCounter::new("creates_total", "Experiences created through the timeline")
+            .unwrap();
+        let failures = IntCounterVec::new(
+            Opts::new("failures_total", "Failed experience operations by kind"),
+            &["kind"],
+        )
+        .unwrap();
+        let upstream_latency = HistogramVec::new(
+            HistogramOpts::new(
+                "upstream_latency_seconds",
+                "Latency of requests to the experiences server",
+            ),
+            &["endpoint"],
+        )
+        .unwrap();
+        registry.register(Box::new(creates.clone())).unwrap();
+        registry.register(Box::new(failures.clone())).unwrap();
+        registry
+            .register(Box::new(upstream_latency.clone()))
+            .unwrap();
+        Metrics {
+            registry,
+            creates,
+            failures,
+            upstream_latency,
+        }
+    }
+
+    pub fn failure(&self, kind: &str) {
+        self.failures.with_label_values(&[kind]).inc();
+    }
+
+    pub fn render(&self) -> String {
+        let mut buffer = Vec::new();
+        TextEncoder::new()
+            .encode(&self.registry.gather(), &mut buffer)
+            .unwrap();
+        String::from_utf8(buffer).unwrap()
+    }
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Token of an `Authorization: Bearer` header, used by scrapers that can not log in.
+pub struct BearerToken(Option<String>);
+
+#[server_api::external::rocket::async_trait]
+impl<'r> FromRequest<'r> for BearerToken {
+    type Error = Infallible;
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        Outcome::Success(BearerToken(
+            request
+                .headers()
+                .get_one("Authorization")
+                .and_then(|v| v.strip_prefix("Bearer "))
+                .map(|v| v.to_string()),
+        ))
+    }
+}
+
+/// Compares in constant time, so the token can not be guessed byte by byte from response times.
+fn token_matches(expected: &str, given: &str) -> bool {
+    expected.len() == given.len()
+        && expected
+            .bytes()
+            .zip(given.bytes())
+            .fold(0, |acc, (a, b)| acc | (a ^ b))
+            == 0
+}
+
+/// Prometheus text exposition of the plugin's counters and histograms. Requires the auth cookie,
+/// or `Authorization: Bearer <metrics_token>` when a `metrics_token` is configured for scrapers.
+#[get("/metrics")]
+pub fn metrics(
+    cookies: &CookieJar<'_>,
+    services: Services<'_>,
+    token: BearerToken,
+) -> Custom<(ContentType, String)> {
+    let scraper = match (&services.experience_config.metrics_token, &token.0) {
+        (Some(expected), Some(given)) => token_matches(expected, given),
+        _ => false,
+    };
+    if !scraper && services.authenticate(cookies).is_err() {
+        return Custom(Status::Unauthorized, (ContentType::Plain, String::new()));
+    }
+    Custom(
+        Status::Ok,
+        (
+            ContentType::new("text", "plain").with_params(("version", "0.0.4")),
+            services.metrics.render(),
+        ),
+    )
+}
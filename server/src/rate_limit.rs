@@ -0,0 +1,309 @@
+use {
+    crate::{
+        i18n::{Locale, Message},
+        is_authenticated,
+        metrics::Metrics,
+        ConfigData,
+    },
+    serde::Deserialize,
+    server_api::external::{
+        rocket::{
+            catch,
+            fairing::AdHoc,
+            http::{Header, Status},
+            request::{FromRequest, Outcome},
+            response::status::Custom,
+            serde::json::Json,
+            Request,
+        },
+        types::api::{APIError, APIResult},
+    },
+    std::{
+        collections::HashMap,
+        sync::{Arc, Mutex},
+        time::{Duration, Instant},
+    },
+};
+
+const WINDOW: Duration = Duration::from_secs(60);
+/// Most addresses tracked at once. A new one beyond it sweeps expired windows, and evicts the oldest if none expired.
+const MAX_TRACKED_CLIENTS: usize = 4096;
+
+/// Limits of the mutating routes and of the requests to the experiences server. A limit of 0 disables it.
+#[derive(Deserialize, Clone)]
+pub struct RateLimitConfig {
+    /// Requests per minute of a single IP address without the password.
+    #[serde(default = "default_per_client")]
+    pub per_client: u32,
+    /// Requests per minute of all callers with the password together. It applies to them only: everyone else is counted
+    /// by address, so they can not lock out the owner.
+    #[serde(default = "default_global")]
+    pub global: u32,
+    /// Requests to the experiences server running at the same time. Further ones wait for a free slot.
+    #[serde(default = "default_max_concurrent_upstream")]
+    pub max_concurrent_upstream: usize,
+}
+
+fn default_per_client() -> u32 {
+    30
+}
+
+fn default_global() -> u32 {
+    120
+}
+
+fn default_max_concurrent_upstream() -> usize {
+    8
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        RateLimitConfig {
+            per_client: default_per_client(),
+            global: default_global(),
+            max_concurrent_upstream: default_max_concurrent_upstream(),
+        }
+    }
+}
+
+/// Fixed one minute window of requests.
+struct Window {
+    start: Instant,
+    count: u32,
+}
+
+impl Window {
+    fn new(now: Instant) -> Self {
+        Window { start: now, count: 0 }
+    }
+
+    /// Time until the window has room again, `None` if it has room now.
+    fn wait(&mut self, now: Instant, limit: u32) -> Option<Duration> {
+        if now.duration_since(self.start) >= WINDOW {
+            *self = Window::new(now);
+        }
+        (limit != 0 && self.count >= limit).then(|| WINDOW - now.duration_since(self.start))
+    }
+}
+
+struct Windows {
+    global: Window,
+    clients: HashMap<String, Window>,
+}
+
+pub struct RateLimiter {
+    config: RateLimitConfig,
+    windows: Mutex<Windows>,
+}
+
+impl RateLimiter {
+    pub fn new(config: RateLimitConfig) -> Self {
+        RateLimiter {
+            config,
+            windows: Mutex::new(Windows {
+                global: Window::new(Instant::now()),
+                clients: HashMap::new(),
+            }),
+        }
+    }
+
+    /// Counts a request in the window of the address `client`, or in the global window for callers with the password,
+    /// who pass `None`. Returns how long to wait if it exceeds the limit, in which case nothing is counted.
+    fn check(&self, client: Option<&str>) -> Result<(), Duration> {
+        let now = Instant::now();
+        let mut guard = self.windows.lock().unwrap();
+        let windows = &mut *guard;
+        let (window, limit) = if let Some(client) = client {
+            if !windows.clients.contains_key(client) && windows.clients.len() >= MAX_TRACKED_CLIENTS {
+                windows
+                    .clients
+                    .retain(|_, v| now.duration_since(v.start) < WINDOW);
+            }
+            // Still full within one window, so the address counting the longest makes room.
+            if !windows.clients.contains_key(client) && windows.clients.len() >= MAX_TRACKED_CLIENTS {
+                let oldest = windows
+                    .clients
+                    .iter()
+                    .min_by_key(|(_, v)| v.start)
+                    .map(|(k, _)| k.clone());
+                if let Some(oldest) = oldest {
+                    windows.clients.remove(&oldest);
+                }
+            }
+            let window = windows
+                .clients
+                .entry(client.to_string())
+                .or_insert_with(|| Window::new(now));
+            (window, self.config.per_client)
+        } else {
+            (&mut windows.global, self.config.global)
+        };
+        if let Some(wait) = window.wait(now, limit) {
+            return Err(wait);
+        }
+        window.count += 1;
+        Ok(())
+    }
+}
+
+/// Seconds a rejected request has to wait, read by the fairing to set `Retry-After`.
+struct RetryAfter(Option<u64>);
+
+/// Passes requests within the rate limits, rejects others with 429 Too Many Requests.
+pub struct RateLimited;
+
+#[server_api::external::rocket::async_trait]
+impl<'r> FromRequest<'r> for RateLimited {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let Some(limiter) = request.rocket().state::<RateLimiter>() else {
+            return Outcome::Error((Status::InternalServerError, ()));
+        };
+        // Made-up credentials must not escape the window of their address, so only a cookie that authenticates counts.
+        let client = if is_authenticated(request) {
+            None
+        } else {
+            Some(match request.client_ip() {
+                Some(ip) => format!("ip:{}", ip),
+                None => "unknown".to_string(),
+            })
+        };
+        match limiter.check(client.as_deref()) {
+            Ok(()) => Outcome::Success(RateLimited),
+            Err(wait) => {
+                if let Some(metrics) = request.rocket().state::<Arc<Metrics>>() {
+                    metrics.failure("rate_limited");
+                }
+                // Rounded up, so a client retrying right on time is not rejected again.
+                let seconds = wait.as_secs() + u64::from(wait.subsec_nanos() > 0);
+                request.local_cache(|| RetryAfter(Some(seconds)));
+                Outcome::Error((Status::TooManyRequests, ()))
+            }
+        }
+    }
+}
+
+/// Adds `Retry-After` to responses of requests rejected by [`RateLimited`].
+pub fn fairing() -> AdHoc {
+    AdHoc::on_response("Retry-After", |request, response| {
+        Box::pin(async move {
+            if response.status() != Status::TooManyRequests {
+                return;
+            }
+            if let RetryAfter(Some(seconds)) = request.local_cache(|| RetryAfter(None)) {
+                response.set_header(Header::new("Retry-After", seconds.to_string()));
+            }
+        })
+    })
+}
+
+/// Answers requests rejected by [`RateLimited`] with an `APIResult` like every other error of the plugin.
+#[catch(429)]
+pub fn too_many_requests(request: &Request<'_>) -> Custom<Json<APIResult<()>>> {
+    let locale = request
+        .rocket()
+        .state::<ConfigData>()
+        .map(|v| v.locale)
+        .unwrap_or_default();
+    let RetryAfter(seconds) = request.local_cache(|| RetryAfter(None));
+    let message = locale.format(
+        Message::TooManyRequests,
+        &seconds.map(|v| v.to_string()).unwrap_or_else(|| "60".to_string()),
+    );
+    Custom(Status::TooManyRequests, Json(Err(APIError::Custom(message))))
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::{test_config, AUTH_COOKIE},
+        server_api::external::rocket::{
+            catchers, http::Cookie, local::asynchronous::Client, post, routes,
+        },
+    };
+
+    #[post("/limited")]
+    fn limited(_rate_limit: RateLimited) -> &'static str {
+        "ok"
+    }
+
+    async fn client(per_client: u32, global: u32) -> Client {
+        let rocket = server_api::external::rocket::build()
+            .manage(test_config("secret"))
+            .manage(RateLimiter::new(RateLimitConfig {
+                per_client,
+                global,
+                max_concurrent_upstream: 0,
+            }))
+            .mount("/", routes![limited])
+            .register("/", catchers![too_many_requests])
+            .attach(fairing());
+        Client::tracked(rocket).await.unwrap()
+    }
+
+    async fn post_with_cookie(client: &Client, password: &str) -> Status {
+        client
+            .post("/limited")
+            .cookie(Cookie::new(AUTH_COOKIE, password.to_string()))
+            .dispatch()
+            .await
+            .status()
+    }
+
+    #[server_api::external::rocket::async_test]
+    async fn rejects_requests_over_the_limit_with_retry_after() {
+        let client = client(2, 0).await;
+        for _ in 0..2 {
+            assert_eq!(client.post("/limited").dispatch().await.status(), Status::Ok);
+        }
+        let response = client.post("/limited").dispatch().await;
+        assert_eq!(response.status(), Status::TooManyRequests);
+        let seconds = response
+            .headers()
+            .get_one("Retry-After")
+            .and_then(|v| v.parse::<u64>().ok())
+            .unwrap();
+        assert!((1..=60).contains(&seconds), "{}", seconds);
+        let body = response.into_json::<APIResult<()>>().await.unwrap();
+        assert!(body.is_err());
+    }
+
+    #[server_api::external::rocket::async_test]
+    async fn made_up_credentials_share_the_address_window() {
+        let client = client(1, 2).await;
+        assert_eq!(post_with_cookie(&client, "guess").await, Status::Ok);
+        assert_eq!(post_with_cookie(&client, "other guess").await, Status::TooManyRequests);
+        // The valid cookie counts against the shared window of the password instead.
+        assert_eq!(post_with_cookie(&client, "secret").await, Status::Ok);
+        assert_eq!(post_with_cookie(&client, "secret").await, Status::Ok);
+        assert_eq!(post_with_cookie(&client, "secret").await, Status::TooManyRequests);
+    }
+
+    #[test]
+    fn unauthenticated_requests_do_not_count_globally() {
+        let limiter = RateLimiter::new(RateLimitConfig {
+            per_client: 0,
+            global: 1,
+            max_concurrent_upstream: 0,
+        });
+        assert!(limiter.check(Some("ip:192.0.2.1")).is_ok());
+        assert!(limiter.check(Some("ip:192.0.2.2")).is_ok());
+        assert!(limiter.check(None).is_ok());
+        assert!(limiter.check(None).is_err());
+    }
+
+    #[test]
+    fn tracked_addresses_are_capped() {
+        let limiter = RateLimiter::new(RateLimitConfig {
+            per_client: 1,
+            global: 0,
+            max_concurrent_upstream: 0,
+        });
+        for i in 0..MAX_TRACKED_CLIENTS + 10 {
+            assert!(limiter.check(Some(&format!("ip:{}", i))).is_ok());
+        }
+        assert_eq!(limiter.windows.lock().unwrap().clients.len(), MAX_TRACKED_CLIENTS);
+    }
+}
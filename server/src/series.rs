@@ -0,0 +1,326 @@
+use {
+    crate::{
+        audit::{self, Actor},
+        create_once,
+        csrf::SameOrigin,
+        experiences::RequestId,
+        is_duplicate_key, plugin_collection,
+        rate_limit::RateLimited,
+        respond, timing_start, CreateExperienceRequest, Created, DatabaseExperience, Services,
+    },
+    serde::{Deserialize, Serialize},
+    server_api::{
+        db::{Database, Event},
+        external::{
+            futures::TryStreamExt,
+            mongodb::{
+                bson::{self, doc, Document},
+                options::{FindOptions, IndexOptions},
+                Collection, IndexModel,
+            },
+            rocket::{
+                http::{CookieJar, Status},
+                post,
+                response::status::Custom,
+                serde::json::Json,
+            },
+            types::{
+                api::{APIError, APIResult},
+                available_plugins::AvailablePlugins,
+                external::chrono::TimeDelta,
+                timing::{TimeRange, Timing},
+            },
+        },
+    },
+    std::{collections::HashMap, time::Duration},
+    tracing::Instrument,
+    uuid::Uuid,
+};
+
+/// Membership of an experience in a series of recurring experiences.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Series {
+    pub id: String,
+    pub name: String,
+    /// Position of the experience in the series, starting at 1.
+    pub number: u32,
+}
+
+impl Series {
+    /// Starts a new series with the experience as its first occurrence.
+    pub fn start(name: String) -> Self {
+        Series {
+            id: Uuid::new_v4().to_string(),
+            name,
+            number: 1,
+        }
+    }
+
+    pub fn badge(&self, members: Option<&Vec<(u32, String)>>) -> SeriesBadge {
+        let neighbor = |number: Option<u32>| {
+            let number = number?;
+            members?
+                .iter()
+                .find(|(v, _)| *v == number)
+                .map(|(_, id)| id.clone())
+        };
+        SeriesBadge {
+            id: self.id.clone(),
+            name: self.name.clone(),
+            number: self.number,
+            previous: neighbor(self.number.checked_sub(1)),
+            next: neighbor(self.number.checked_add(1)),
+        }
+    }
+}
+
+/// Series of an experience as shown on its card, with the ids of the neighboring occurrences.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct SeriesBadge {
+    pub id: String,
+    pub name: String,
+    pub number: u32,
+    pub previous: Option<String>,
+    pub next: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct MemberProjection {
+    event: DatabaseExperience,
+}
+
+fn series_filter(series_ids: Document) -> Document {
+    Database::combine_documents(
+        Database::generate_find_plugin_filter(AvailablePlugins::timeline_plugin_experience),
+        doc! {"event.series.id": series_ids},
+    )
+}
+
+/// Number and experience id of every occurrence of the given series, grouped by series id.
+pub async fn find_members(
+    events: &Collection<Document>,
+    series_ids: Vec<String>,
+) -> APIResult<HashMap<String, Vec<(u32, String)>>> {
+    let mut result: HashMap<String, Vec<(u32, String)>> = HashMap::new();
+    if series_ids.is_empty() {
+        return Ok(result);
+    }
+    let options = FindOptions::builder()
+        .projection(doc! {"_id": 0, "event": 1})
+        .build();
+    let members: Vec<MemberProjection> = events
+        .clone_with_type::<MemberProjection>()
+        .find(series_filter(doc! {"$in": series_ids}), options)
+        .await?
+        .try_collect()
+        .await?;
+    for member in members {
+        if let Some(series) = member.event.series {
+            result
+                .entry(series.id)
+                .or_default()
+                .push((series.number, member.event.id));
+        }
+    }
+    Ok(result)
+}
+
+#[derive(Deserialize)]
+pub struct NextOccurrenceRequest {
+    pub series_id: String,
+    /// Defaults to the previous occurrence moved by the distance between the last two occurrences, or by a week.
+    #[serde(default)]
+    pub time: Option<Timing>,
+    #[serde(default)]
+    pub idempotency_key: Option<String>,
+}
+
+/// Timing of the occurrence following `latest`, which holds the last occurrences of a series, newest first. `None` if it
+/// is out of range.
+fn next_timing(latest: &[Event<DatabaseExperience>]) -> Option<Timing> {
+    let last = &latest[0].timing;
+    let interval = match latest.get(1) {
+        Some(previous) if timing_start(&previous.timing) < timing_start(last) => {
+            timing_start(last) - timing_start(&previous.timing)
+        }
+        _ => TimeDelta::try_weeks(1).unwrap(),
+    };
+    Some(match last {
+        Timing::Instant(v) => Timing::Instant(v.checked_add_signed(interval)?),
+        Timing::Range(v) => Timing::Range(TimeRange {
+            start: v.start.checked_add_signed(interval)?,
+            end: v.end.checked_add_signed(interval)?,
+        }),
+    })
+}
+
+/// Numbers tried when concurrent requests create occurrences of the same series.
+const MAX_ATTEMPTS: u32 = 5;
+const SERIES_NUMBERS: &str = "series_numbers";
+/// Longest a number stays claimed by a create that never finished, for example because the process died.
+const RESERVATION_TIMEOUT_SECS: u64 = 600;
+
+/// Expires reservations left behind by creates that never finished.
+pub(crate) async fn ensure_indexes(
+    database: &Database,
+) -> Result<(), server_api::external::mongodb::error::Error> {
+    plugin_collection::<Document>(database, SERIES_NUMBERS)
+        .create_index(
+            IndexModel::builder()
+                .keys(doc! {"reserved_at": 1})
+                .options(
+                    IndexOptions::builder()
+                        .name("series_number_reservation_ttl".to_string())
+                        .expire_after(Duration::from_secs(RESERVATION_TIMEOUT_SECS))
+                        .build(),
+                )
+                .build(),
+            None,
+        )
+        .await?;
+    Ok(())
+}
+
+/// Claims `number` in the series while the occurrence is created upstream. `false` if another request holds it or an
+/// occurrence already has it. Reservations only cover creates in flight, the stored occurrences keep their numbers
+/// through the unique index on the events.
+async fn reserve_number(
+    services: &Services<'_>,
+    series_id: &str,
+    number: u32,
+) -> Result<bool, (Status, APIError)> {
+    let reservations = plugin_collection::<Document>(services.database, SERIES_NUMBERS);
+    let reservation = format!("{}#{}", series_id, number);
+    match reservations
+        .insert_one(doc! {"_id": &reservation, "reserved_at": bson::DateTime::now()}, None)
+        .await
+    {
+        Ok(_) => {}
+        Err(e) if is_duplicate_key(&e) => return Ok(false),
+        Err(e) => return Err((Status::InternalServerError, e.into())),
+    }
+    // A request finishing in between stored its occurrence before giving up its reservation.
+    let taken = services
+        .database
+        .get_events::<Document>()
+        .find_one(
+            Database::combine_documents(
+                series_filter(doc! {"$eq": series_id}),
+                doc! {"event.series.number": number},
+            ),
+            None,
+        )
+        .await
+        .map_err(|e| (Status::InternalServerError, e.into()))?
+        .is_some();
+    if taken {
+        let _ = reservations.delete_one(doc! {"_id": reservation}, None).await;
+    }
+    Ok(!taken)
+}
+
+/// Creates the next occurrence of a series, named after the series and numbered, with the tags, color and icon of the last one.
+/// The number follows the highest occurrence that still exists. Concurrent requests get consecutive numbers: each claims
+/// its number first and moves on to the next one if it is taken.
+async fn next_occurrence(
+    services: &Services<'_>,
+    request: NextOccurrenceRequest,
+    request_id: &RequestId,
+) -> Result<Created, (Status, APIError)> {
+    let latest: Vec<Event<DatabaseExperience>> = services
+        .database
+        .get_events::<DatabaseExperience>()
+        .find(
+            series_filter(doc! {"$eq": &request.series_id}),
+            FindOptions::builder()
+                .sort(doc! {"event.series.number": -1})
+                .limit(2)
+                .build(),
+        )
+        .instrument(tracing::info_span!("database", operation = "find_series"))
+        .await
+        .map_err(|e| (Status::InternalServerError, e.into()))?
+        .try_collect()
+        .await
+        .map_err(|e| (Status::InternalServerError, e.into()))?;
+    let Some(last) = latest.first() else {
+        return Err((
+            Status::NotFound,
+            APIError::Custom(format!("Unknown series: {}", request.series_id)),
+        ));
+    };
+    let Some(last_series) = &last.event.series else {
+        return Err((
+            Status::InternalServerError,
+            APIError::Custom(format!("Series without members: {}", request.series_id)),
+        ));
+    };
+    let time = match request.time {
+        Some(time) => time,
+        None => next_timing(&latest).ok_or_else(|| {
+            (
+                Status::BadRequest,
+                APIError::Custom(format!(
+                    "The next occurrence of the series {} is out of range",
+                    request.series_id
+                )),
+            )
+        })?,
+    };
+    let mut number = last_series.number + 1;
+    while !reserve_number(services, &last_series.id, number).await? {
+        if number - last_series.number >= MAX_ATTEMPTS {
+            return Err((
+                Status::Conflict,
+                APIError::Custom(format!(
+                    "Too many concurrent occurrences of the series {}",
+                    request.series_id
+                )),
+            ));
+        }
+        number += 1;
+    }
+    let series = Series {
+        id: last_series.id.clone(),
+        name: last_series.name.clone(),
+        number,
+    };
+    let reservation = format!("{}#{}", series.id, number);
+
+    let create_request = CreateExperienceRequest {
+        name: format!("{} #{}", series.name, series.number),
+        time,
+        tags: last.event.tags.clone(),
+        idempotency_key: request.idempotency_key,
+        color: last.event.color.clone(),
+        icon: last.event.icon.clone(),
+        parent_id: None,
+        series_name: None,
+        sources: Vec::new(),
+    };
+    let result = create_once(services, &create_request, Some(series), request_id).await;
+    // Either the occurrence holds the number now, or it was not used and the next request may take it.
+    let _ = plugin_collection::<Document>(services.database, SERIES_NUMBERS)
+        .delete_one(doc! {"_id": reservation}, None)
+        .await;
+    result
+}
+
+#[post("/series/next", data = "<request>")]
+pub async fn create_next_occurrence(
+    _same_origin: SameOrigin,
+    _rate_limit: RateLimited,
+    request: Json<NextOccurrenceRequest>,
+    cookies: &CookieJar<'_>,
+    services: Services<'_>,
+    request_id: RequestId,
+    actor: Actor,
+) -> Custom<Json<APIResult<String>>> {
+    let result = async {
+        services.authenticate(cookies)?;
+        next_occurrence(&services, request.into_inner(), &request_id).await
+    }
+    .await;
+    audit::record_created(&services, &actor, "create_next_occurrence", &request_id, None, &result).await;
+    respond(result.map(Created::into_id))
+}
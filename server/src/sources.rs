@@ -0,0 +1,285 @@
+use {
+    crate::{
+        audit::{self, Actor},
+        color, create_once, csrf::SameOrigin, experiences::RequestId, rate_limit::RateLimited,
+        respond, timing_end, timing_start, CreateExperienceRequest, Created, Services,
+    },
+    serde::{Deserialize, Serialize},
+    server_api::{
+        db::{Database, Event},
+        external::{
+            futures::{StreamExt, TryStreamExt},
+            mongodb::bson::{doc, Document},
+            rocket::{
+                http::{CookieJar, Status},
+                post,
+                response::status::Custom,
+                serde::json::Json,
+            },
+            types::{
+                api::{APIError, APIResult},
+                available_plugins::AvailablePlugins,
+                external::{reqwest, serde_json},
+                timing::{TimeRange, Timing},
+            },
+            url::form_urlencoded,
+        },
+    },
+    std::{collections::HashMap, io, time::Duration},
+    tracing::Instrument,
+};
+
+/// Timeline event of another plugin an experience was built from.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct EventReference {
+    pub plugin: AvailablePlugins,
+    pub id: String,
+}
+
+/// Most events one experience can be built from.
+const MAX_EVENTS: usize = 200;
+/// Longest a media source may take to deliver one file.
+const MEDIA_TIMEOUT_SECS: u64 = 120;
+
+/// Name of a plugin as used for the keys of `media_sources` in the config.
+fn plugin_key(plugin: &AvailablePlugins) -> Option<String> {
+    serde_json::to_value(plugin)
+        .ok()?
+        .as_str()
+        .map(|v| v.to_string())
+}
+
+impl EventReference {
+    fn plugin_name(&self) -> Option<String> {
+        plugin_key(&self.plugin)
+    }
+}
+
+#[derive(Deserialize)]
+pub struct CreateFromEventsRequest {
+    pub name: String,
+    pub events: Vec<EventReference>,
+    #[serde(default)]
+    pub idempotency_key: Option<String>,
+    #[serde(default)]
+    pub color: Option<String>,
+    #[serde(default)]
+    pub icon: Option<String>,
+}
+
+/// Smallest timing covering all events. A single instant stays an instant.
+fn covering(timings: &[Timing]) -> Option<Timing> {
+    let start = timings.iter().map(timing_start).min()?;
+    let end = timings.iter().map(timing_end).max()?;
+    Some(if start == end {
+        Timing::Instant(start)
+    } else {
+        Timing::Range(TimeRange { start, end })
+    })
+}
+
+/// Timings of the referenced events, loaded with one query. Fails if any of them is unknown.
+async fn find_timings(
+    services: &Services<'_>,
+    events: &[EventReference],
+) -> Result<Vec<Timing>, (Status, APIError)> {
+    if events.len() > MAX_EVENTS {
+        return Err((
+            Status::BadRequest,
+            APIError::Custom(format!(
+                "An experience can be built from at most {} events",
+                MAX_EVENTS
+            )),
+        ));
+    }
+    let mut by_plugin: HashMap<Option<String>, (AvailablePlugins, Vec<&str>)> = HashMap::new();
+    for reference in events {
+        by_plugin
+            .entry(reference.plugin_name())
+            .or_insert_with(|| (reference.plugin.clone(), Vec::new()))
+            .1
+            .push(&reference.id);
+    }
+    if by_plugin.is_empty() {
+        return Ok(Vec::new());
+    }
+    let filters = by_plugin
+        .into_values()
+        .map(|(plugin, ids)| {
+            Database::combine_documents(
+                Database::generate_find_plugin_filter(plugin),
+                doc! {"id": {"$in": ids}},
+            )
+        })
+        .collect::<Vec<_>>();
+    let found: Vec<Event<Document>> = services
+        .database
+        .get_events::<Document>()
+        .find(doc! {"$or": filters}, None)
+        .await
+        .map_err(|e| (Status::InternalServerError, e.into()))?
+        .try_collect()
+        .await
+        .map_err(|e| (Status::InternalServerError, e.into()))?;
+    let timings = found
+        .into_iter()
+        .map(|v| ((plugin_key(&v.plugin), v.id), v.timing))
+        .collect::<HashMap<_, _>>();
+    events
+        .iter()
+        .map(|reference| {
+            timings
+                .get(&(reference.plugin_name(), reference.id.clone()))
+                .cloned()
+                .ok_or_else(|| {
+                    (
+                        Status::NotFound,
+                        APIError::Custom(format!("Unknown event: {}", reference.id)),
+                    )
+                })
+        })
+        .collect()
+}
+
+/// File name of forwarded media, with an extension guessed from the content type.
+fn media_filename(reference: &EventReference, content_type: Option<&str>) -> String {
+    let extension = match content_type.and_then(|v| v.split(['/', ';']).nth(1)) {
+        Some("jpeg") => "jpg",
+        Some("quicktime") => "mov",
+        Some(v) => v,
+        None => "bin",
+    };
+    format!("{}.{}", reference.id, extension)
+}
+
+/// Streams the media of a referenced event from the URL configured for its plugin to the experience `id`.
+/// Events of plugins without a configured media source are skipped.
+async fn forward_media(
+    services: &Services<'_>,
+    client: &reqwest::Client,
+    id: &str,
+    reference: &EventReference,
+    request_id: &RequestId,
+) -> Result<(), String> {
+    let Some(template) = reference
+        .plugin_name()
+        .and_then(|v| services.experience_config.media_sources.get(&v))
+    else {
+        return Ok(());
+    };
+    let url = template.replace(
+        "{id}",
+        &form_urlencoded::byte_serialize(reference.id.as_bytes()).collect::<String>(),
+    );
+    let response = client
+        .get(&url)
+        .send()
+        .await
+        .and_then(|v| v.error_for_status())
+        .map_err(|e| e.to_string())?;
+    let max_mb = services.experience_config.max_upload_mb;
+    let limit = max_mb.saturating_mul(1024 * 1024);
+    let too_large = move || format!("Media is larger than {} MiB", max_mb);
+    if response.content_length().is_some_and(|v| v > limit) {
+        return Err(too_large());
+    }
+    // A missing or wrong `Content-Length` is caught while streaming.
+    let mut received = 0u64;
+    let body = response.bytes_stream().map(move |chunk| {
+        let chunk = chunk.map_err(io::Error::other)?;
+        received += chunk.len() as u64;
+        if received > limit {
+            return Err(io::Error::other(too_large()));
+        }
+        Ok(chunk)
+    });
+    let filename = media_filename(
+        reference,
+        response
+            .headers()
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok()),
+    );
+    let query = form_urlencoded::Serializer::new(String::new())
+        .append_pair("filename", &filename)
+        .finish();
+    services
+        .experiences
+        .post_body::<()>(
+            "upload_media",
+            &format!("/api/experience/{}/media?{}", id, query),
+            reqwest::Body::wrap_stream(body),
+            request_id,
+        )
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Creates an experience covering the referenced events and forwards their media.
+/// Media that can not be forwarded is logged and skipped, the experience is kept either way.
+#[tracing::instrument(name = "create_from_events", skip_all, fields(request_id = %request_id, events = request.events.len()))]
+async fn create_from(
+    services: &Services<'_>,
+    request: CreateFromEventsRequest,
+    request_id: &RequestId,
+) -> Result<Created, (Status, APIError)> {
+    let timings = find_timings(services, &request.events).await?;
+    let time = covering(&timings).ok_or_else(|| {
+        (
+            Status::BadRequest,
+            APIError::Custom("No events selected".to_string()),
+        )
+    })?;
+
+    let create_request = CreateExperienceRequest {
+        name: request.name,
+        time,
+        tags: Vec::new(),
+        idempotency_key: request.idempotency_key,
+        color: request.color,
+        icon: request.icon,
+        parent_id: None,
+        series_name: None,
+        sources: request.events,
+    };
+    let id = match create_once(services, &create_request, None, request_id).await? {
+        // A replay, the media was forwarded by the first request.
+        Created::Existing(id) => return Ok(Created::Existing(id)),
+        Created::New(id) => id,
+    };
+
+    let client = reqwest::Client::builder()
+        .timeout(Duration::from_secs(MEDIA_TIMEOUT_SECS))
+        .build()
+        .map_err(|e| (Status::InternalServerError, APIError::Custom(e.to_string())))?;
+    for reference in &create_request.sources {
+        if let Err(e) = forward_media(services, &client, &id, reference, request_id)
+            .instrument(tracing::info_span!("forward_media", event_id = %reference.id))
+            .await
+        {
+            services.metrics.failure("media_source");
+            tracing::warn!(event_id = %reference.id, error = %e, "unable to forward media");
+        }
+    }
+    color::refresh(services, &id, request_id).await;
+    Ok(Created::New(id))
+}
+
+#[post("/create_from_events", data = "<request>")]
+pub async fn create_from_events(
+    _same_origin: SameOrigin,
+    _rate_limit: RateLimited,
+    request: Json<CreateFromEventsRequest>,
+    cookies: &CookieJar<'_>,
+    services: Services<'_>,
+    request_id: RequestId,
+    actor: Actor,
+) -> Custom<Json<APIResult<String>>> {
+    let result = async {
+        services.authenticate(cookies)?;
+        create_from(&services, request.into_inner(), &request_id).await
+    }
+    .await;
+    audit::record_created(&services, &actor, "create_from_events", &request_id, None, &result).await;
+    respond(result.map(Created::into_id))
+}
@@ -0,0 +1,270 @@
+use {
+    crate::{respond, Services},
+    serde::{Deserialize, Serialize},
+    server_api::{
+        db::Database,
+        external::{
+            futures::TryStreamExt,
+            mongodb::bson::{self, doc, Document},
+            rocket::{
+                get,
+                http::{CookieJar, Status},
+                response::status::Custom,
+                serde::json::Json,
+            },
+            types::{
+                api::{APIError, APIResult},
+                available_plugins::AvailablePlugins,
+                external::chrono::{DateTime, Utc},
+                timing::TimeRange,
+            },
+        },
+    },
+};
+
+const TOP_LIMIT: i64 = 10;
+const GAP_LIMIT: i64 = 5;
+
+#[derive(Serialize)]
+pub struct PeriodCount {
+    /// `2024-W07` for weeks, `2024-02` for months.
+    pub period: String,
+    pub count: u64,
+}
+
+#[derive(Serialize)]
+pub struct Gap {
+    pub from: DateTime<Utc>,
+    pub to: DateTime<Utc>,
+    pub minutes: i64,
+}
+
+#[derive(Serialize)]
+pub struct ValueCount {
+    pub value: String,
+    pub count: u64,
+}
+
+#[derive(Serialize)]
+pub struct Stats {
+    pub weeks: Vec<PeriodCount>,
+    pub months: Vec<PeriodCount>,
+    pub range_experiences: u64,
+    /// Summed length of all experiences with a range timing.
+    pub range_minutes: i64,
+    pub longest_gaps: Vec<Gap>,
+    pub top_tags: Vec<ValueCount>,
+    pub top_names: Vec<ValueCount>,
+}
+
+#[derive(Deserialize)]
+struct Period {
+    year: i32,
+    number: u32,
+}
+
+#[derive(Deserialize)]
+struct PeriodGroup {
+    #[serde(rename = "_id")]
+    period: Period,
+    count: u64,
+}
+
+#[derive(Deserialize)]
+struct DurationGroup {
+    count: u64,
+    total_ms: i64,
+}
+
+#[derive(Deserialize)]
+struct GapRow {
+    from: bson::DateTime,
+    to: bson::DateTime,
+    ms: i64,
+}
+
+#[derive(Deserialize)]
+struct ValueGroup {
+    #[serde(rename = "_id")]
+    value: String,
+    count: u64,
+}
+
+#[derive(Deserialize)]
+struct Facets {
+    weeks: Vec<PeriodGroup>,
+    months: Vec<PeriodGroup>,
+    durations: Vec<DurationGroup>,
+    gaps: Vec<GapRow>,
+    tags: Vec<ValueGroup>,
+    names: Vec<ValueGroup>,
+}
+
+fn to_chrono(v: bson::DateTime) -> DateTime<Utc> {
+    DateTime::from_timestamp_millis(v.timestamp_millis()).unwrap_or_default()
+}
+
+fn top(field: &str) -> Vec<Document> {
+    vec![
+        doc! {"$group": {"_id": field, "count": {"$sum": 1}}},
+        doc! {"$sort": {"count": -1, "_id": 1}},
+        doc! {"$limit": TOP_LIMIT},
+    ]
+}
+
+/// One aggregation with a facet per statistic. Weeks and months are counted in `timezone`.
+fn pipeline(range: &TimeRange, timezone: &str) -> Vec<Document> {
+    let group_by = |year: &str, number: &str| {
+        doc! {"$group": {
+            "_id": {
+                "year": {year: {"date": "$start", "timezone": timezone}},
+                "number": {number: {"date": "$start", "timezone": timezone}},
+            },
+            "count": {"$sum": 1},
+        }}
+    };
+    let mut tags = vec![doc! {"$unwind": "$tags"}];
+    tags.extend(top("$tags"));
+    vec![
+        doc! {"$match": Database::combine_documents(
+            Database::generate_range_filter(range),
+            Database::generate_find_plugin_filter(AvailablePlugins::timeline_plugin_experience),
+        )},
+        // Instants count as experiences without a duration.
+        doc! {"$project": {
+            "start": {"$toDate": {"$ifNull": ["$timing.Range.start", "$timing.Instant"]}},
+            "end": {"$toDate": {"$ifNull": ["$timing.Range.end", "$timing.Instant"]}},
+            "is_range": {"$ne": [{"$type": "$timing.Range"}, "missing"]},
+            "name": "$event.name",
+            "tags": {"$ifNull": ["$event.tags", []]},
+        }},
+        doc! {"$facet": {
+            "weeks": [group_by("$isoWeekYear", "$isoWeek"), doc! {"$sort": {"_id.year": 1, "_id.number": 1}}],
+            "months": [group_by("$year", "$month"), doc! {"$sort": {"_id.year": 1, "_id.number": 1}}],
+            "durations": [
+                {"$match": {"is_range": true}},
+                {"$group": {
+                    "_id": null,
+                    "count": {"$sum": 1},
+                    "total_ms": {"$sum": {"$toLong": {"$subtract": ["$end", "$start"]}}},
+                }},
+            ],
+            // The latest end of all earlier experiences, not only of the previous one, so a long experience
+            // covering shorter ones does not count their ends as the start of a gap.
+            "gaps": [
+                {"$setWindowFields": {
+                    "sortBy": {"start": 1},
+                    "output": {"previous_end": {
+                        "$max": "$end",
+                        "window": {"documents": ["unbounded", -1]},
+                    }},
+                }},
+                {"$match": {"previous_end": {"$ne": null}}},
+                {"$project": {
+                    "from": "$previous_end",
+                    "to": "$start",
+                    "ms": {"$toLong": {"$subtract": ["$start", "$previous_end"]}},
+                }},
+                {"$match": {"ms": {"$gt": 0}}},
+                {"$sort": {"ms": -1}},
+                {"$limit": GAP_LIMIT},
+            ],
+            "tags": tags,
+            "names": top("$name"),
+        }},
+    ]
+}
+
+async fn stats(services: &Services<'_>, range: &TimeRange) -> APIResult<Stats> {
+    let config = services.experience_config;
+    let facets: Vec<Document> = services
+        .database
+        .get_events::<Document>()
+        .aggregate(pipeline(range, config.timezone.name()), None)
+        .await?
+        .try_collect()
+        .await?;
+    let facets: Facets = facets
+        .into_iter()
+        .next()
+        .map(bson::from_document)
+        .transpose()
+        .map_err(|e| APIError::Custom(e.to_string()))?
+        .ok_or_else(|| APIError::Custom("Empty statistics".to_string()))?;
+
+    let counts = |groups: Vec<ValueGroup>| {
+        groups
+            .into_iter()
+            .map(|v| ValueCount {
+                value: v.value,
+                count: v.count,
+            })
+            .collect()
+    };
+    let durations = facets.durations.first();
+    Ok(Stats {
+        weeks: facets
+            .weeks
+            .into_iter()
+            .map(|v| PeriodCount {
+                period: format!("{}-W{:02}", v.period.year, v.period.number),
+                count: v.count,
+            })
+            .collect(),
+        months: facets
+            .months
+            .into_iter()
+            .map(|v| PeriodCount {
+                period: format!("{}-{:02}", v.period.year, v.period.number),
+                count: v.count,
+            })
+            .collect(),
+        range_experiences: durations.map(|v| v.count).unwrap_or_default(),
+        range_minutes: durations.map(|v| v.total_ms / 60_000).unwrap_or_default(),
+        longest_gaps: facets
+            .gaps
+            .into_iter()
+            .map(|v| Gap {
+                from: to_chrono(v.from),
+                to: to_chrono(v.to),
+                minutes: v.ms / 60_000,
+            })
+            .collect(),
+        top_tags: counts(facets.tags),
+        top_names: counts(facets.names),
+    })
+}
+
+#[get("/stats?<start>&<end>")]
+pub async fn get_stats(
+    start: &str,
+    end: &str,
+    cookies: &CookieJar<'_>,
+    services: Services<'_>,
+) -> Custom<Json<APIResult<Stats>>> {
+    respond(
+        async {
+            services.authenticate(cookies)?;
+            let parse = |v: &str| {
+                DateTime::parse_from_rfc3339(v)
+                    .map(|v| v.with_timezone(&Utc))
+                    .map_err(|e| (Status::BadRequest, APIError::Custom(format!("Invalid date {}: {}", v, e))))
+            };
+            let range = TimeRange {
+                start: parse(start)?,
+                end: parse(end)?,
+            };
+            if range.end < range.start {
+                return Err((
+                    Status::BadRequest,
+                    APIError::Custom("Invalid range: the end is before the start".to_string()),
+                ));
+            }
+            stats(&services, &range).await.map_err(|e| {
+                services.metrics.failure("database");
+                (Status::InternalServerError, e)
+            })
+        }
+        .await,
+    )
+}
@@ -0,0 +1,214 @@
+use {
+    crate::{DatabaseExperience, ExperienceProjection},
+    serde::Serialize,
+    server_api::{
+        config::Config,
+        db::Database,
+        external::{
+            futures::StreamExt,
+            mongodb::{bson::doc, options::FindOptions},
+            rocket::{
+                get,
+                http::{CookieJar, Status},
+                response::status::{self, Custom},
+                serde::json::Json,
+                State,
+            },
+            types::{
+                api::{APIError, APIResult},
+                available_plugins::AvailablePlugins,
+                external::chrono::{DateTime, TimeDelta, Utc},
+                timing::{TimeRange, Timing},
+            },
+        },
+        web::auth,
+    },
+    std::{collections::HashMap, sync::Arc},
+};
+
+const MAX_NAMES: usize = 8;
+/// Shorter prefixes match most of the collection, so nothing is suggested for them.
+const MIN_PREFIX_CHARS: usize = 2;
+/// Experiences scanned per query. Names are ranked among the most recently created ones.
+const MAX_CANDIDATES: i64 = 500;
+/// Half-life of a single use of a name when ranking. Frequent names win, but old habits fade.
+const HALF_LIFE_DAYS: f64 = 90.0;
+/// How far from the requested timing an experience counts as "near".
+const NEAR_DAYS: i64 = 2;
+/// Share of the whole name that has to match, as normalized Levenshtein similarity, for a possible duplicate.
+const MIN_SIMILARITY: f64 = 0.75;
+/// Shorter inputs are within a few edits of too many names to flag any of them.
+const MIN_SIMILAR_CHARS: usize = 4;
+
+#[derive(Serialize)]
+pub struct NameSuggestion {
+    pub name: String,
+    pub count: u32,
+    pub last_used: DateTime<Utc>,
+}
+
+#[derive(Serialize)]
+pub struct SimilarExperience {
+    pub id: String,
+    pub name: String,
+    pub time: Timing,
+}
+
+#[derive(Serialize)]
+pub struct Suggestions {
+    /// Previously used names starting with the prefix, best first.
+    pub names: Vec<NameSuggestion>,
+    /// Experiences near the requested timing whose whole name resembles the input. Likely duplicates.
+    pub similar: Vec<SimilarExperience>,
+}
+
+fn escape_regex(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        if "\\^$.|?*+()[]{}".contains(c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+fn normalize(name: &str) -> String {
+    name.trim().to_lowercase()
+}
+
+/// Whether the whole of `name` is close to the whole `input`, so a partial input does not flag every name containing it.
+fn is_similar(name: &str, input: &str) -> bool {
+    let (name, input) = (normalize(name), normalize(input));
+    input.chars().count() >= MIN_SIMILAR_CHARS
+        && (name == input || strsim::normalized_levenshtein(&name, &input) >= MIN_SIMILARITY)
+}
+
+async fn find_experiences(
+    database: &Database,
+    filter: server_api::external::mongodb::bson::Document,
+) -> APIResult<Vec<ExperienceProjection>> {
+    let filter = Database::combine_documents(
+        Database::generate_find_plugin_filter(AvailablePlugins::timeline_plugin_experience),
+        filter,
+    );
+    // `_id` is generated on insert, so the newest experiences come first.
+    let options = FindOptions::builder()
+        .projection(doc! {"_id": 0, "timing": 1, "event": 1})
+        .sort(doc! {"_id": -1})
+        .limit(MAX_CANDIDATES)
+        .build();
+    let mut cursor = database
+        .get_events::<DatabaseExperience>()
+        .clone_with_type::<ExperienceProjection>()
+        .find(filter, options)
+        .await?;
+    let mut result = Vec::new();
+    while let Some(v) = cursor.next().await {
+        result.push(v?);
+    }
+    Ok(result)
+}
+
+async fn suggestions(
+    database: &Database,
+    prefix: &str,
+    around: Option<DateTime<Utc>>,
+) -> APIResult<Suggestions> {
+    if prefix.trim().chars().count() < MIN_PREFIX_CHARS {
+        return Ok(Suggestions {
+            names: Vec::new(),
+            similar: Vec::new(),
+        });
+    }
+    let now = Utc::now();
+    let mut ranked: HashMap<String, (f64, NameSuggestion)> = HashMap::new();
+    for experience in find_experiences(
+        database,
+        doc! {"event.name": {"$regex": format!("^{}", escape_regex(prefix.trim())), "$options": "i"}},
+    )
+    .await?
+    {
+        let time = crate::timing_start(&experience.timing);
+        let age_days = (now - time).num_seconds().max(0) as f64 / 86_400.0;
+        let (score, suggestion) = ranked
+            .entry(normalize(&experience.event.name))
+            .or_insert_with(|| {
+                (
+                    0.0,
+                    NameSuggestion {
+                        name: experience.event.name.clone(),
+                        count: 0,
+                        last_used: time,
+                    },
+                )
+            });
+        *score += 0.5f64.powf(age_days / HALF_LIFE_DAYS);
+        suggestion.count += 1;
+        if time > suggestion.last_used {
+            suggestion.last_used = time;
+            suggestion.name = experience.event.name;
+        }
+    }
+    let mut names = ranked.into_values().collect::<Vec<_>>();
+    names.sort_by(|a, b| b.0.total_cmp(&a.0));
+    let names = names
+        .into_iter()
+        .take(MAX_NAMES)
+        .map(|(_, v)| v)
+        .collect();
+
+    let similar = match around {
+        Some(around) => {
+            let range = TimeRange {
+                start: around - TimeDelta::try_days(NEAR_DAYS).unwrap(),
+                end: around + TimeDelta::try_days(NEAR_DAYS).unwrap(),
+            };
+            find_experiences(database, Database::generate_range_filter(&range))
+                .await?
+                .into_iter()
+                .filter(|v| is_similar(&v.event.name, prefix))
+                .map(|v| SimilarExperience {
+                    id: v.event.id,
+                    name: v.event.name,
+                    time: v.timing,
+                })
+                .collect()
+        }
+        _ => Vec::new(),
+    };
+
+    Ok(Suggestions { names, similar })
+}
+
+/// Names for the create form, empty for prefixes shorter than `MIN_PREFIX_CHARS`. `around` is an RFC 3339 timestamp; experiences with a similar name close to it are reported as possible duplicates.
+#[get("/suggest?<prefix>&<around>")]
+pub async fn suggest(
+    prefix: String,
+    around: Option<String>,
+    cookies: &CookieJar<'_>,
+    config: &State<Config>,
+    database: &State<Arc<Database>>,
+) -> Custom<Json<APIResult<Suggestions>>> {
+    if auth(cookies, config).is_err() {
+        return status::Custom(
+            Status::Unauthorized,
+            Json(Err(APIError::AuthenticationError)),
+        );
+    }
+
+    let around = match around.map(|v| DateTime::parse_from_rfc3339(&v)).transpose() {
+        Ok(v) => v.map(|v| v.with_timezone(&Utc)),
+        Err(e) => {
+            return status::Custom(
+                Status::BadRequest,
+                Json(Err(APIError::Custom(format!("Invalid around parameter: {}", e)))),
+            )
+        }
+    };
+
+    match suggestions(database, &prefix, around).await {
+        Ok(v) => status::Custom(Status::Ok, Json(Ok(v))),
+        Err(e) => status::Custom(Status::InternalServerError, Json(Err(e))),
+    }
+}
@@ -0,0 +1,296 @@
+use {
+    crate::{
+        audit::{self, Actor, AuditEntry},
+        color, create_once,
+        csrf::SameOrigin,
+        experiences::RequestId,
+        i18n::Locale,
+        rate_limit::RateLimited,
+        plugin_collection, respond, timing_start, CreateExperienceRequest, Created, Services,
+    },
+    chrono_tz::Tz,
+    serde::{Deserialize, Serialize},
+    server_api::external::{
+        futures::TryStreamExt,
+        mongodb::{
+            bson::{doc, Document},
+            options::ReplaceOptions,
+        },
+        rocket::{
+            get,
+            http::{CookieJar, Status},
+            post,
+            response::status::Custom,
+            serde::json::Json,
+        },
+        types::{
+            api::{APIError, APIResult},
+            external::chrono::TimeDelta,
+            timing::{TimeRange, Timing},
+        },
+    },
+};
+
+/// Longest duration a template can give its experiences, a year.
+const MAX_DURATION_MINUTES: i64 = 366 * 24 * 60;
+
+/// Preset for creating experiences with one click. Defined in the plugin config or stored through `POST /templates`.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ExperienceTemplate {
+    /// Unique name of the template, shown on its button.
+    pub name: String,
+    /// Name of the created experience. `{date}` and `{time}` are replaced with the start of the experience in the configured
+    /// time zone, any other placeholder with the text the user entered.
+    pub pattern: String,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Turns an instant into a range of this length.
+    #[serde(default)]
+    pub duration_minutes: Option<i64>,
+    #[serde(default)]
+    pub color: Option<String>,
+    #[serde(default)]
+    pub icon: Option<String>,
+}
+
+impl ExperienceTemplate {
+    fn validate(&self) -> Result<(), (Status, APIError)> {
+        let invalid = |message: String| Err((Status::BadRequest, APIError::Custom(message)));
+        if self.name.trim().is_empty() || self.pattern.trim().is_empty() {
+            return invalid("A template needs a name and a pattern".to_string());
+        }
+        if let Some(color) = self.color.as_deref().filter(|v| !color::is_valid_color(v)) {
+            return invalid(format!("Invalid color: {}", color));
+        }
+        if let Some(icon) = self.icon.as_deref().filter(|v| !color::is_valid_icon(v)) {
+            return invalid(format!("Invalid icon: {}", icon));
+        }
+        if self
+            .duration_minutes
+            .is_some_and(|v| !(1..=MAX_DURATION_MINUTES).contains(&v))
+        {
+            return invalid(format!(
+                "The duration of a template has to be between 1 and {} minutes",
+                MAX_DURATION_MINUTES
+            ));
+        }
+        Ok(())
+    }
+
+    /// Replaces the placeholders of `pattern`. Unclosed braces are kept as they are.
+    fn expand_name(&self, time: &Timing, text: &str, locale: Locale, timezone: Tz) -> String {
+        let start = timing_start(time).with_timezone(&timezone);
+        let date_format = match locale {
+            Locale::En => "%Y-%m-%d",
+            Locale::De => "%d.%m.%Y",
+        };
+        let mut result = String::new();
+        let mut rest = self.pattern.as_str();
+        while let Some(open) = rest.find('{') {
+            let Some(close) = rest[open..].find('}') else {
+                break;
+            };
+            result.push_str(&rest[..open]);
+            match &rest[open + 1..open + close] {
+                "date" => result.push_str(&start.format(date_format).to_string()),
+                "time" => result.push_str(&start.format("%H:%M").to_string()),
+                _ => result.push_str(text.trim()),
+            }
+            rest = &rest[open + close + 1..];
+        }
+        result.push_str(rest);
+        result
+    }
+
+    /// Builds the full create request for an experience starting at `time`. Fails if its end is out of range.
+    pub fn expand(
+        &self,
+        time: Timing,
+        text: &str,
+        locale: Locale,
+        timezone: Tz,
+        idempotency_key: Option<String>,
+    ) -> Result<CreateExperienceRequest, (Status, APIError)> {
+        let name = self.expand_name(&time, text, locale, timezone);
+        let time = match (time, self.duration_minutes) {
+            (Timing::Instant(start), Some(minutes)) => {
+                let end = TimeDelta::try_minutes(minutes)
+                    .and_then(|v| start.checked_add_signed(v))
+                    .ok_or_else(|| {
+                        (
+                            Status::BadRequest,
+                            APIError::Custom(format!(
+                                "The template {} ends out of range",
+                                self.name
+                            )),
+                        )
+                    })?;
+                Timing::Range(TimeRange { start, end })
+            }
+            (time, _) => time,
+        };
+        Ok(CreateExperienceRequest {
+            name,
+            time,
+            tags: self.tags.clone(),
+            idempotency_key,
+            color: self.color.clone(),
+            icon: self.icon.clone(),
+            parent_id: None,
+            series_name: None,
+            sources: Vec::new(),
+        })
+    }
+}
+
+/// Checks the templates of the plugin config, so a broken one fails the start instead of every create using it.
+pub fn validate_config(templates: &[ExperienceTemplate]) -> Result<(), String> {
+    for (index, template) in templates.iter().enumerate() {
+        template
+            .validate()
+            .map_err(|(_, e)| format!("template {}: {}", template.name, e))?;
+        if templates[..index].iter().any(|v| v.name == template.name) {
+            return Err(format!("template {} is defined twice", template.name));
+        }
+    }
+    Ok(())
+}
+
+async fn all_templates(services: &Services<'_>) -> Result<Vec<ExperienceTemplate>, (Status, APIError)> {
+    let mut templates = services.experience_config.templates.clone();
+    let stored: Vec<ExperienceTemplate> = plugin_collection::<ExperienceTemplate>(services.database, "templates")
+        .find(None, None)
+        .await
+        .map_err(|e| (Status::InternalServerError, e.into()))?
+        .try_collect()
+        .await
+        .map_err(|e| (Status::InternalServerError, e.into()))?;
+    // Templates from the config win over stored ones with the same name.
+    for template in stored {
+        if !templates.iter().any(|v| v.name == template.name) {
+            templates.push(template);
+        }
+    }
+    Ok(templates)
+}
+
+#[get("/templates")]
+pub async fn list_templates(
+    cookies: &CookieJar<'_>,
+    services: Services<'_>,
+) -> Custom<Json<APIResult<Vec<ExperienceTemplate>>>> {
+    respond(
+        async {
+            services.authenticate(cookies)?;
+            all_templates(&services).await
+        }
+        .await,
+    )
+}
+
+/// Stores a template, replacing a stored one with the same name. Templates from the config can not be replaced.
+#[post("/templates", data = "<template>")]
+pub async fn save_template(
+    _same_origin: SameOrigin,
+    _rate_limit: RateLimited,
+    template: Json<ExperienceTemplate>,
+    cookies: &CookieJar<'_>,
+    services: Services<'_>,
+    request_id: RequestId,
+    actor: Actor,
+) -> Custom<Json<APIResult<()>>> {
+    let mut before = None;
+    let mut after = None;
+    let result = async {
+        services.authenticate(cookies)?;
+        template.validate()?;
+        if services
+            .experience_config
+            .templates
+            .iter()
+            .any(|v| v.name == template.name)
+        {
+            return Err((
+                Status::Conflict,
+                APIError::Custom(format!(
+                    "The template {} is defined in the config",
+                    template.name
+                )),
+            ));
+        }
+        let mut document = server_api::external::mongodb::bson::to_document(&template.0)
+            .map_err(|e| (Status::InternalServerError, APIError::Custom(e.to_string())))?;
+        document.insert("_id", &template.name);
+        let templates = plugin_collection::<Document>(services.database, "templates");
+        before = templates
+            .find_one(doc! {"_id": &template.name}, None)
+            .await
+            .map_err(|e| (Status::InternalServerError, e.into()))?;
+        templates
+            .replace_one(
+                doc! {"_id": &template.name},
+                document.clone(),
+                ReplaceOptions::builder().upsert(true).build(),
+            )
+            .await
+            .map_err(|e| (Status::InternalServerError, e.into()))?;
+        after = Some(document);
+        Ok(())
+    }
+    .await;
+    let entry = AuditEntry::new(&actor, "save_template", &request_id)
+        .target(Some(&template.name))
+        .before(before)
+        .after(after)
+        .result(&result);
+    audit::record(services.database, entry).await;
+    respond(result)
+}
+
+#[derive(Deserialize)]
+pub struct CreateFromTemplateRequest {
+    pub template: String,
+    pub time: Timing,
+    /// Replaces the custom placeholders of the pattern, for example `{place}`.
+    #[serde(default)]
+    pub text: String,
+    #[serde(default)]
+    pub idempotency_key: Option<String>,
+}
+
+#[post("/create_from_template", data = "<request>")]
+pub async fn create_from_template(
+    _same_origin: SameOrigin,
+    _rate_limit: RateLimited,
+    request: Json<CreateFromTemplateRequest>,
+    cookies: &CookieJar<'_>,
+    services: Services<'_>,
+    request_id: RequestId,
+    actor: Actor,
+) -> Custom<Json<APIResult<String>>> {
+    let result = async {
+        services.authenticate(cookies)?;
+        let request = request.into_inner();
+        let template = all_templates(&services)
+            .await?
+            .into_iter()
+            .find(|v| v.name == request.template)
+            .ok_or_else(|| {
+                (
+                    Status::NotFound,
+                    APIError::Custom(format!("Unknown template: {}", request.template)),
+                )
+            })?;
+        let create_request = template.expand(
+            request.time,
+            &request.text,
+            services.experience_config.locale,
+            services.experience_config.timezone,
+            request.idempotency_key,
+        )?;
+        create_once(&services, &create_request, None, &request_id).await
+    }
+    .await;
+    audit::record_created(&services, &actor, "create_from_template", &request_id, None, &result).await;
+    respond(result.map(Created::into_id))
+}
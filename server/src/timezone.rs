@@ -0,0 +1,62 @@
+use {
+    crate::{respond, Services},
+    chrono_tz::Tz,
+    serde::Serialize,
+    server_api::external::{
+        rocket::{get, http::CookieJar, response::status::Custom, serde::json::Json},
+        types::{
+            api::APIResult,
+            external::chrono::{DateTime, LocalResult, TimeDelta, TimeZone, Timelike, Utc},
+            timing::TimeRange,
+        },
+    },
+};
+
+pub fn default_timezone() -> Tz {
+    Tz::UTC
+}
+
+/// Start of every local wall-clock hour of `timezone` within `range`.
+/// Hours skipped by a daylight saving change are left out, repeated ones appear twice.
+pub fn hourly_slots(range: &TimeRange, timezone: Tz) -> Vec<DateTime<Utc>> {
+    let local_start = range.start.with_timezone(&timezone).naive_local();
+    let mut current = local_start
+        .date()
+        .and_hms_opt(local_start.hour(), 0, 0)
+        .unwrap();
+    let mut result = Vec::new();
+    loop {
+        let candidates = match timezone.from_local_datetime(&current) {
+            LocalResult::Single(v) => vec![v],
+            LocalResult::Ambiguous(a, b) => vec![a, b],
+            LocalResult::None => Vec::new(),
+        };
+        for candidate in candidates {
+            let candidate = candidate.with_timezone(&Utc);
+            if candidate >= range.end {
+                return result;
+            }
+            if candidate >= range.start {
+                result.push(candidate);
+            }
+        }
+        current += TimeDelta::try_hours(1).unwrap();
+    }
+}
+
+/// Settings the client needs to render the timeline like the server generates it.
+#[derive(Serialize)]
+pub struct ClientConfig {
+    /// IANA name of the configured time zone.
+    pub timezone: String,
+}
+
+#[get("/config")]
+pub async fn client_config(
+    cookies: &CookieJar<'_>,
+    services: Services<'_>,
+) -> Custom<Json<APIResult<ClientConfig>>> {
+    respond(services.authenticate(cookies).map(|_| ClientConfig {
+        timezone: services.experience_config.timezone.name().to_string(),
+    }))
+}
@@ -0,0 +1,212 @@
+use {
+    crate::{
+        audit::{self, Actor},
+        color, create_once, csrf::SameOrigin, experience_filter, experiences::RequestId,
+        live::LiveUpdate, media_date, plugin_collection, rate_limit::RateLimited, respond, Created,
+        CreateExperienceRequest, DatabaseExperience, Services,
+    },
+    server_api::external::{
+        mongodb::bson::{doc, Document},
+        rocket::{
+            data::{Data, ToByteUnit},
+            http::{CookieJar, Status},
+            post,
+            response::status::Custom,
+            serde::json::Json,
+            tokio::{fs, task},
+        },
+        types::{
+            api::{APIError, APIResult},
+            external::{
+                chrono::{DateTime, Utc},
+                reqwest,
+            },
+            timing::Timing,
+        },
+        url::form_urlencoded,
+    },
+    std::path::{Path, PathBuf},
+    tokio_util::io::ReaderStream,
+    uuid::Uuid,
+};
+
+pub fn default_max_upload_mb() -> u64 {
+    1024
+}
+
+/// Removes the spooled upload when the request is done, however it ends.
+pub(crate) struct TempUpload(pub PathBuf);
+
+impl Drop for TempUpload {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.0);
+    }
+}
+
+/// Only the last path component of a client-supplied file name is kept.
+pub(crate) fn sanitize_filename(filename: &str) -> Option<String> {
+    Path::new(filename)
+        .file_name()
+        .and_then(|v| v.to_str())
+        .filter(|v| !v.is_empty())
+        .map(|v| v.to_string())
+}
+
+/// Streams a file to the experiences server as media of the experience `id`.
+pub(crate) async fn upload_media(
+    services: &Services<'_>,
+    id: &str,
+    filename: &str,
+    path: &Path,
+    request_id: &RequestId,
+) -> Result<(), (Status, APIError)> {
+    let file = fs::File::open(path)
+        .await
+        .map_err(|e| (Status::InternalServerError, APIError::Custom(e.to_string())))?;
+    let query = form_urlencoded::Serializer::new(String::new())
+        .append_pair("filename", filename)
+        .finish();
+    services
+        .experiences
+        .post_body::<()>(
+            "upload_media",
+            &format!("/api/experience/{}/media?{}", id, query),
+            reqwest::Body::wrap_stream(ReaderStream::new(file)),
+            request_id,
+        )
+        .await
+        .map_err(|e| {
+            services.metrics.failure(e.kind());
+            (
+                Status::BadGateway,
+                e.to_api_error(services.experience_config.locale),
+            )
+        })
+}
+
+/// Removes an experience created for an upload that could not be forwarded, so the user is not left with an empty one.
+/// The idempotency key is released too, a retry with it creates the experience again. Failures are only logged.
+async fn discard(services: &Services<'_>, id: &str, idempotency_key: Option<&str>, request_id: &RequestId) {
+    if let Err(e) = services
+        .experiences
+        .post::<_, ()>("delete", &format!("/api/experience/{}/delete", id), &(), request_id)
+        .await
+    {
+        services.metrics.failure(e.kind());
+        tracing::error!(experience_id = %id, "unable to delete experience of failed upload: {}", e);
+    }
+    let removed = services
+        .database
+        .get_events::<DatabaseExperience>()
+        .delete_one(experience_filter(id), None)
+        .await;
+    if let Some(key) = idempotency_key {
+        let _ = plugin_collection::<Document>(services.database, "idempotency_keys")
+            .delete_one(doc! {"_id": key}, None)
+            .await;
+    }
+    match removed {
+        Ok(_) => services.live_updates.send(LiveUpdate::Removed { id: id.to_string() }),
+        Err(e) => tracing::error!(experience_id = %id, "unable to remove experience of failed upload: {}", e),
+    }
+}
+
+/// Writes the request body to a temporary file, so it can be inspected before it is forwarded.
+pub(crate) async fn spool(
+    services: &Services<'_>,
+    data: Data<'_>,
+) -> Result<TempUpload, (Status, APIError)> {
+    let upload = TempUpload(
+        std::env::temp_dir().join(format!("timeline_plugin_experience_{}", Uuid::new_v4())),
+    );
+    let written = data
+        .open(services.experience_config.max_upload_mb.mebibytes())
+        .into_file(&upload.0)
+        .await
+        .map_err(|e| (Status::InternalServerError, APIError::Custom(e.to_string())))?;
+    if !written.is_complete() {
+        return Err((
+            Status::PayloadTooLarge,
+            APIError::Custom(format!(
+                "Uploads are limited to {} MiB",
+                services.experience_config.max_upload_mb
+            )),
+        ));
+    }
+    Ok(upload)
+}
+
+/// Creates an experience from a photo or video. It starts when the file was taken, or at `fallback` if the file does not tell, and gets the file as its first media.
+/// If the file can not be forwarded, the experience is removed again.
+#[post("/upload?<filename>&<name>&<fallback>&<idempotency_key>", data = "<data>")]
+#[allow(clippy::too_many_arguments)]
+pub async fn create_from_upload(
+    _same_origin: SameOrigin,
+    _rate_limit: RateLimited,
+    filename: &str,
+    name: Option<&str>,
+    fallback: &str,
+    idempotency_key: Option<String>,
+    data: Data<'_>,
+    cookies: &CookieJar<'_>,
+    services: Services<'_>,
+    request_id: RequestId,
+    actor: Actor,
+) -> Custom<Json<APIResult<String>>> {
+    let result = async {
+        services.authenticate(cookies)?;
+        let filename = sanitize_filename(filename).ok_or_else(|| {
+            (
+                Status::BadRequest,
+                APIError::Custom("Missing file name".to_string()),
+            )
+        })?;
+        let fallback = DateTime::parse_from_rfc3339(fallback)
+            .map_err(|e| (Status::BadRequest, APIError::Custom(e.to_string())))?
+            .with_timezone(&Utc);
+
+        let upload = spool(&services, data).await?;
+        let path = upload.0.clone();
+        let timezone = services.experience_config.timezone;
+        let taken = task::spawn_blocking(move || media_date::creation_date(&path, timezone))
+            .await
+            .ok()
+            .flatten();
+
+        let name = name
+            .map(|v| v.trim().to_string())
+            .filter(|v| !v.is_empty())
+            .unwrap_or_else(|| {
+                Path::new(&filename)
+                    .file_stem()
+                    .and_then(|v| v.to_str())
+                    .unwrap_or(&filename)
+                    .to_string()
+            });
+        let request = CreateExperienceRequest {
+            name,
+            time: Timing::Instant(taken.unwrap_or(fallback)),
+            tags: Vec::new(),
+            idempotency_key,
+            color: None,
+            icon: None,
+            parent_id: None,
+            series_name: None,
+            sources: Vec::new(),
+        };
+        let id = match create_once(&services, &request, None, &request_id).await? {
+            // A replay of an upload that already went through, the file is there.
+            Created::Existing(id) => return Ok(Created::Existing(id)),
+            Created::New(id) => id,
+        };
+        if let Err(e) = upload_media(&services, &id, &filename, &upload.0, &request_id).await {
+            discard(&services, &id, request.idempotency_key.as_deref(), &request_id).await;
+            return Err(e);
+        }
+        color::refresh(&services, &id, &request_id).await;
+        Ok(Created::New(id))
+    }
+    .await;
+    audit::record_created(&services, &actor, "create_from_upload", &request_id, None, &result).await;
+    respond(result.map(Created::into_id))
+}
@@ -0,0 +1,307 @@
+use {
+    crate::{
+        audit::{self, Actor, AuditEntry},
+        color,
+        experiences::{ExperiencesClient, RequestId},
+        live::{LiveUpdate, LiveUpdates},
+        metrics::Metrics,
+        experience_filter, plugin_collection, ConfigData, DatabaseExperience,
+    },
+    hmac::{Hmac, Mac},
+    serde::Deserialize,
+    server_api::{
+        db::Database,
+        external::{
+            mongodb::{
+                bson::{self, doc, Document},
+                options::UpdateOptions,
+            },
+            rocket::{
+                http::Status,
+                post,
+                request::{FromRequest, Outcome},
+                response::status::{self, Custom},
+                serde::json::Json,
+                Request, State,
+            },
+            types::{
+                api::{APIError, APIResult},
+                external::serde_json,
+                timing::Timing,
+            },
+        },
+    },
+    sha2::Sha256,
+    std::{convert::Infallible, sync::Arc},
+    tracing::Instrument,
+};
+
+pub const SIGNATURE_HEADER: &str = "X-Experiences-Signature";
+
+/// Raw value of the `X-Experiences-Signature` header: `sha256=<hex encoded HMAC of the body>`.
+pub struct WebhookSignature(Option<String>);
+
+#[server_api::external::rocket::async_trait]
+impl<'r> FromRequest<'r> for WebhookSignature {
+    type Error = Infallible;
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        Outcome::Success(WebhookSignature(
+            request
+                .headers()
+                .get_one(SIGNATURE_HEADER)
+                .map(|v| v.to_string()),
+        ))
+    }
+}
+
+impl WebhookSignature {
+    fn verify(&self, secret: &str, body: &[u8]) -> bool {
+        let Some(signature) = self
+            .0
+            .as_deref()
+            .and_then(|v| v.strip_prefix("sha256="))
+            .and_then(|v| hex::decode(v).ok())
+        else {
+            return false;
+        };
+        let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).unwrap();
+        mac.update(body);
+        mac.verify_slice(&signature).is_ok()
+    }
+}
+
+#[derive(Deserialize, Clone, Copy, Debug)]
+pub enum ExperienceChange {
+    Created,
+    Renamed,
+    Deleted,
+    CoverChanged,
+}
+
+/// State of the experience after the change. Missing for deletions.
+#[derive(Deserialize)]
+pub struct WebhookExperience {
+    pub name: String,
+    pub time: Timing,
+}
+
+/// Every notification carries the full state of the experience, so applying the newest one is enough to converge, no matter which ones before it were lost or reordered.
+#[derive(Deserialize)]
+pub struct WebhookNotification {
+    /// Increases with every change of an experience. Deliveries with a sequence number not above the last applied one are ignored.
+    pub sequence: u64,
+    pub experience_id: String,
+    pub change: ExperienceChange,
+    pub experience: Option<WebhookExperience>,
+}
+
+/// Matches the stored state of an experience only if it was written by an older notification, or not by any.
+fn older_than(sequence: i64) -> Document {
+    doc! {"$or": [
+        {"webhook_sequence": {"$lt": sequence}},
+        {"webhook_sequence": {"$exists": false}},
+    ]}
+}
+
+/// Writes the state of a notification to the timeline events. Returns the update for live clients, `None` if the notification was stale.
+/// Every write is conditional on the sequence stored with the event, so of two concurrent deliveries the newer state always wins.
+async fn apply(
+    database: &Database,
+    experiences: &ExperiencesClient,
+    notification: &WebhookNotification,
+    sequence: i64,
+    request_id: &RequestId,
+) -> Result<Option<LiveUpdate>, APIError> {
+    // Deleted experiences have no event left to hold their sequence.
+    let deletions = plugin_collection::<Document>(database, "webhook_deletions");
+    let deleted_since = doc! {"_id": &notification.experience_id, "sequence": {"$gte": sequence}};
+    let events = database
+        .get_events::<DatabaseExperience>()
+        .clone_with_type::<Document>();
+    let filter = experience_filter(&notification.experience_id);
+    let update = match (notification.change, &notification.experience) {
+        (ExperienceChange::Deleted, _) => {
+            deletions
+                .update_one(
+                    doc! {"_id": &notification.experience_id},
+                    doc! {"$max": {"sequence": sequence}},
+                    UpdateOptions::builder().upsert(true).build(),
+                )
+                .await?;
+            let deleted = events
+                .delete_one(
+                    Database::combine_documents(filter.clone(), older_than(sequence)),
+                    None,
+                )
+                .await?;
+            if deleted.deleted_count == 0 && events.find_one(filter, None).await?.is_some() {
+                return Ok(None);
+            }
+            LiveUpdate::Removed {
+                id: notification.experience_id.clone(),
+            }
+        }
+        (_, Some(experience)) => {
+            if deletions.find_one(deleted_since.clone(), None).await?.is_some() {
+                return Ok(None);
+            }
+            let timing = bson::to_bson(&experience.time)
+                .map_err(|e| APIError::Custom(format!("Unable to serialize timing: {}", e)))?;
+            let mut set = doc! {
+                "timing": timing,
+                "event.name": &experience.name,
+                "event.id": &notification.experience_id,
+                "webhook_sequence": sequence,
+            };
+            if let ExperienceChange::CoverChanged = notification.change {
+                match color::cover_color(experiences, &notification.experience_id, request_id).await {
+                    Ok(Some(color)) => {
+                        set.insert("event.cover_color", color);
+                    }
+                    Ok(None) => {}
+                    Err(e) => tracing::warn!("unable to derive cover color: {}", e),
+                }
+            }
+            let updated = events
+                .update_one(
+                    Database::combine_documents(filter.clone(), older_than(sequence)),
+                    doc! {"$set": set.clone()},
+                    None,
+                )
+                .await?;
+            if updated.matched_count == 0 {
+                // Either a newer notification was applied already, or the experience is not on the timeline yet.
+                let inserted = events
+                    .update_one(
+                        filter.clone(),
+                        doc! {"$setOnInsert": set},
+                        UpdateOptions::builder().upsert(true).build(),
+                    )
+                    .await?;
+                if inserted.upserted_id.is_none() {
+                    return Ok(None);
+                }
+            }
+            // A deletion recorded while this write was in flight may have found nothing to delete yet. Deletions are
+            // recorded before they delete, so either it removed this write or it is seen here and the write is undone.
+            if deletions.find_one(deleted_since, None).await?.is_some() {
+                events
+                    .delete_one(
+                        Database::combine_documents(filter, doc! {"webhook_sequence": sequence}),
+                        None,
+                    )
+                    .await?;
+                return Ok(None);
+            }
+
+            let stored = database
+                .get_events::<DatabaseExperience>()
+                .find_one(filter, None)
+                .await?
+                .ok_or_else(|| APIError::Custom("Updated experience disappeared".to_string()))?;
+            LiveUpdate::Changed {
+                title: stored.event.name.clone(),
+                time: stored.timing,
+                data: stored.event.to_compressed(),
+            }
+        }
+        (change, None) => {
+            return Err(APIError::Custom(format!(
+                "Notification of type {:?} is missing the experience state",
+                change
+            )))
+        }
+    };
+    Ok(Some(update))
+}
+
+/// Receives change notifications from the experiences server. The body is authenticated with an HMAC-SHA256 using `webhook_secret` from the plugin config.
+#[post("/webhook", data = "<body>")]
+pub async fn webhook(
+    body: Vec<u8>,
+    signature: WebhookSignature,
+    database: &State<Arc<Database>>,
+    experience_config: &State<ConfigData>,
+    experiences: &State<ExperiencesClient>,
+    metrics: &State<Arc<Metrics>>,
+    live_updates: &State<LiveUpdates>,
+    request_id: RequestId,
+) -> Custom<Json<APIResult<()>>> {
+    let Some(secret) = &experience_config.webhook_secret else {
+        return status::Custom(
+            Status::NotFound,
+            Json(Err(APIError::Custom(
+                "No webhook secret is configured for the experiences plugin".to_string(),
+            ))),
+        );
+    };
+
+    if !signature.verify(secret, &body) {
+        metrics.failure("webhook_signature");
+        return status::Custom(
+            Status::Unauthorized,
+            Json(Err(APIError::AuthenticationError)),
+        );
+    }
+
+    let notification = match serde_json::from_slice::<WebhookNotification>(&body) {
+        Ok(v) => v,
+        Err(e) => {
+            return status::Custom(
+                Status::BadRequest,
+                Json(Err(APIError::SerdeJsonError(format!(
+                    "Unable to read webhook notification: {}",
+                    e
+                )))),
+            )
+        }
+    };
+
+    // Sequences are stored as BSON integers, larger ones can not be compared.
+    let Ok(sequence) = i64::try_from(notification.sequence) else {
+        return status::Custom(
+            Status::BadRequest,
+            Json(Err(APIError::Custom(format!(
+                "Sequence {} is out of range",
+                notification.sequence
+            )))),
+        );
+    };
+
+    let span = tracing::info_span!(
+        "webhook",
+        experience_id = %notification.experience_id,
+        sequence = notification.sequence,
+        change = ?notification.change,
+    );
+    let before = audit::snapshot(database, &notification.experience_id).await;
+    let result = match apply(database, experiences, &notification, sequence, &request_id)
+        .instrument(span)
+        .await
+    {
+        Ok(Some(update)) => {
+            live_updates.send(update);
+            Ok(())
+        }
+        Ok(None) => {
+            tracing::debug!(experience_id = %notification.experience_id, "ignored stale webhook delivery");
+            return status::Custom(Status::Ok, Json(Ok(())));
+        }
+        Err(e) => {
+            metrics.failure("webhook_apply");
+            Err((Status::InternalServerError, e))
+        }
+    };
+    let action = format!("webhook_{:?}", notification.change).to_lowercase();
+    let entry = AuditEntry::new(&Actor::webhook(), &action, &request_id)
+        .target(Some(&notification.experience_id))
+        .before(before)
+        .after(audit::snapshot(database, &notification.experience_id).await)
+        .result(&result);
+    audit::record(database, entry).await;
+    match result {
+        Ok(()) => status::Custom(Status::Ok, Json(Ok(()))),
+        Err((status, e)) => status::Custom(status, Json(Err(e))),
+    }
+}